@@ -15,10 +15,28 @@ create table guest_token (
 
 create table room (
 	id integer primary key autoincrement check (id > 0),
+	button integer not null default 0 check (
+		0 <= button
+		and button < 10
+	),
 	sb integer not null default 0 check (
 		0 <= sb
 		and sb < 10
-	)
+	),
+	bb integer not null default 0 check (
+		0 <= bb
+		and bb < 10
+	),
+	small_blind integer not null default 1 check (small_blind > 0),
+	big_blind integer not null default 2 check (big_blind >= small_blind),
+	ante integer not null default 0 check (ante >= 0),
+	big_blind_ante integer not null default false check (big_blind_ante in (true, false)),
+	starting_stack integer not null default 1000 check (starting_stack >= min_stack),
+	min_stack integer not null default 10 check (min_stack > 0),
+	-- bumped by every change of the room or its games
+	version integer not null default 0 check (version >= 0),
+	-- rooms of imported games are read-only
+	imported integer not null default false check (imported in (true, false))
 ) strict;
 
 create table seat (
@@ -29,8 +47,9 @@ create table seat (
 	),
 	guest_id integer references guest (id),
 	ready integer not null default false check (ready in (true, false)),
-	stack integer not null default 1000 check (stack >= 0),
+	stack integer not null check (stack >= 0),
 	bet integer not null default 0 check (bet >= 0),
+	round_bet integer not null default 0 check (round_bet >= 0),
 	fold integer not null default false check (fold in (true, false)),
 	unique (room_id, guest_id),
 	unique (room_id, position)
@@ -47,18 +66,42 @@ create table game (
 		0 <= position
 		and position < 10
 	),
-	raise_position integer not null default 0 check (
-		0 <= raise_position
-		and raise_position < 10
+	min_raise integer not null default 0 check (min_raise >= 0),
+	acted integer not null default 0 check (acted >= 0),
+	aggressor integer check (
+		aggressor is null
+		or (
+			0 <= aggressor
+			and aggressor < 10
+		)
 	),
+	button integer not null default 0 check (
+		0 <= button
+		and button < 10
+	),
+	-- UTC time when the game started
+	created_at text not null default (datetime('now')),
 	unique (id, room_id)
 ) strict;
 
 create table hand (
 	game_id integer references game (id),
 	guest_id integer references guest (id),
-	c1 text not null check (length (c1) = 2),
-	c2 text not null check (length (c2) = 2),
+	position integer not null check (
+		0 <= position
+		and position < 10
+	),
+	-- stack before antes and blinds
+	stack integer not null check (stack >= 0),
+	-- null if unknown, only for imported games
+	c1 text check (
+		c1 is null
+		or length (c1) = 2
+	),
+	c2 text check (
+		c2 is null
+		or length (c2) = 2
+	),
 	unique (game_id, guest_id)
 ) strict;
 
@@ -86,9 +129,88 @@ create table bet (
 	unique (game_id, guest_id)
 ) strict;
 
+create table record (
+	game_id integer references game (id),
+	seq integer not null check (seq >= 0),
+	round text not null check (
+		round in ('preflop', 'flop', 'turn', 'river', 'finish')
+	),
+	position integer check (
+		position is null
+		or (
+			0 <= position
+			and position < 10
+		)
+	),
+	guest_id integer references guest (id),
+	action text not null check (
+		action in (
+			'ante',
+			'sb',
+			'bb',
+			'check',
+			'call',
+			'bet',
+			'raise',
+			'allin',
+			'fold',
+			'deal',
+			'show',
+			'muck'
+		)
+	),
+	chips integer not null default 0 check (chips >= 0),
+	cards text not null default '',
+	unique (game_id, seq)
+) strict;
+
 create table result (
 	game_id integer references game (id),
 	guest_id integer references guest (id),
 	diff integer not null,
+	winner integer not null default false check (winner in (true, false)),
+	uncalled integer not null default 0 check (uncalled >= 0),
 	unique (game_id, guest_id)
 ) strict;
+
+create table pot (
+	game_id integer references game (id),
+	number integer not null check (number >= 0),
+	guest_id integer references guest (id),
+	won integer not null default 0 check (won >= 0),
+	unique (game_id, number, guest_id)
+) strict;
+
+create table equity (
+	game_id integer references game (id),
+	-- cards of this round are dealt
+	round text not null check (round in ('preflop', 'flop', 'turn', 'river')),
+	guest_id integer references guest (id),
+	position integer not null check (
+		0 <= position
+		and position < 10
+	),
+	-- percentages
+	win real not null check (
+		0 <= win
+		and win <= 100
+	),
+	tie real not null check (
+		0 <= tie
+		and tie <= 100
+	),
+	equity real not null check (
+		0 <= equity
+		and equity <= 100
+	),
+	unique (game_id, round, guest_id)
+) strict;
+
+create table event (
+	room_id integer references room (id),
+	seq integer not null check (seq > 0),
+	-- only visible to this guest if not null
+	guest_id integer references guest (id),
+	data text not null,
+	unique (room_id, seq)
+) strict;
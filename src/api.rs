@@ -1,6 +1,9 @@
 pub mod game;
 pub mod guest;
+pub mod hub;
 pub mod room;
+pub mod sse;
+pub mod ws;
 
 pub use game::game_api;
 pub use guest::guest_api;
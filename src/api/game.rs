@@ -1,24 +1,77 @@
-use actix_web::{HttpResponse, get, post, web};
+use actix_web::{HttpResponse, get, patch, post, web};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use serde::Deserialize;
 use serde_json::json;
 use tracing::info;
 
+use super::hub::publish;
 use crate::{
 	Round,
 	db::{
-		bet as execute_bet, calc_result, fold as execute_fold, game_by_id, get_common, get_flop,
-		get_hand, get_results, get_river, get_turn, guest_by_id, guest_by_token, new_transaction,
-		open_connection, room_by_id, update_round,
+		game_by_id, get_common, get_dealt_players, get_equities, get_hand, get_pots, get_records,
+		get_results, get_shown_guests, guest_by_id, guest_by_token, import_history, insert_events,
+		is_imported, load_history, load_state, new_transaction, open_connection, room_by_id,
+		save_state,
 	},
+	engine::{Event, GameState, PlayerAction, RoomEvent},
 	error::{Result, bad_request_error, forbidden_error, not_found_error, unauthorized_error},
+	history::{HandHistory, ohh::Ohh},
 };
 
+#[derive(Deserialize)]
+struct ActionForm {
+	/// One of check, call, bet, raise_to, all_in, fold and show
+	action: String,
+	/// Chips to bet or the total bet to raise to
+	amount: Option<usize>,
+}
+
 #[derive(Deserialize)]
 struct BetForm {
 	chips: usize,
 }
 
+/// A move of a player, before the game is loaded
+#[derive(PartialEq)]
+enum Move {
+	Action(PlayerAction),
+	/// Chips to put in, the action is told from the state of the game
+	Chips(usize),
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+	format: String,
+}
+
+#[derive(Deserialize)]
+struct RecordsQuery {
+	#[serde(default)]
+	offset: usize,
+}
+
+/// Play an action as the current player, the chips are computed by the server
+///
+/// Any player of the game can show the hand after the game is over.
+#[patch("/{game_id}/records")]
+pub async fn play(
+	auth: BearerAuth,
+	path: web::Path<usize>,
+	form: web::Form<ActionForm>,
+) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
+	info!("patch: {} for game {game_id}", form.action);
+
+	let action = PlayerAction::parse(&form.action, form.amount)?;
+	let (state, round_changed) = apply_move(auth.token(), game_id, Move::Action(action))?;
+
+	Ok(HttpResponse::Ok()
+		.json(json!({"room": state.room, "game": state.game, "round_changed": round_changed})))
+}
+
+/// Put chips in as the current player
+///
+/// Kept for older clients, prefer `PATCH /games/{game_id}/records`.
 #[post("/{game_id}/bets")]
 pub async fn bet(
 	auth: BearerAuth,
@@ -28,69 +81,135 @@ pub async fn bet(
 	let game_id = path.into_inner();
 	info!("post: bet for game {game_id}");
 
-	let mut conn = open_connection()?;
-	let tx = new_transaction(&mut conn)?;
-
-	let guest = guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
-	let mut game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
-
-	if game.is_over() {
-		return Err(forbidden_error("game is already finished"));
-	}
-
-	let mut room = room_by_id(&tx, game.room_id)?.unwrap();
-	let player = room.get_guest(game.position).unwrap();
-
-	if guest.id != player.id {
-		return Err(forbidden_error("it's not your turn, please wait"));
-	}
-
-	execute_bet(&tx, &mut room, &mut game, form.chips)?;
-	let round_changed = update_round(&tx, &room, &mut game)?;
-	if game.is_over() {
-		calc_result(&tx, &mut room, &game)?;
-	}
-
-	tx.commit()?;
+	let (state, round_changed) = apply_move(auth.token(), game_id, Move::Chips(form.chips))?;
 
 	Ok(HttpResponse::Created()
-		.json(json!({"room": room, "game": game, "round_changed": round_changed})))
+		.json(json!({"room": state.room, "game": state.game, "round_changed": round_changed})))
 }
 
+/// Fold as the current player
+///
+/// Kept for older clients, prefer `PATCH /games/{game_id}/records`.
 #[post("/{game_id}/fold")]
 pub async fn fold(auth: BearerAuth, path: web::Path<usize>) -> Result<HttpResponse> {
 	let game_id = path.into_inner();
 	info!("post: fold for game {game_id}");
 
+	let (state, round_changed) =
+		apply_move(auth.token(), game_id, Move::Action(PlayerAction::Fold))?;
+
+	Ok(HttpResponse::Created()
+		.json(json!({"room": state.room, "game": state.game, "round_changed": round_changed})))
+}
+
+/// Apply the move of the guest, persist and publish the events
+///
+/// # Return
+///
+/// The state after the move and whether the round changed
+fn apply_move(token: &str, game_id: usize, player_move: Move) -> Result<(GameState, bool)> {
 	let mut conn = open_connection()?;
 	let tx = new_transaction(&mut conn)?;
 
-	let guest = guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
-	let mut game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
-
-	if game.is_over() {
-		return Err(forbidden_error("game is already finished"));
+	let guest = guest_by_token(&tx, token)?.ok_or(unauthorized_error("invalid token"))?;
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	if is_imported(&tx, game.room_id)? {
+		return Err(forbidden_error("imported games are read-only"));
 	}
+	let mut state = load_state(&tx, game_id)?.unwrap();
+
+	let mut events = if player_move == Move::Action(PlayerAction::Show) {
+		state.show(guest.id)?
+	} else {
+		if state.game.is_over() {
+			return Err(forbidden_error("game is already finished"));
+		}
+
+		if state
+			.current_guest()
+			.is_none_or(|player| player.id != guest.id)
+		{
+			return Err(forbidden_error("it's not your turn, please wait"));
+		}
+
+		let action = match player_move {
+			Move::Action(action) => action,
+			Move::Chips(chips) => state.action_for_chips(chips),
+		};
+		state.apply(action)?
+	};
+	let round_changed = events.iter().any(|e| matches!(e, Event::Round(_)));
+	save_state(&tx, &state, &mut events)?;
+
+	let room_events = RoomEvent::from_game(&state, &events);
+	let room_events = insert_events(&tx, state.room.id, room_events)?;
+	tx.commit()?;
+	publish(state.room.id, room_events);
 
-	let mut room = room_by_id(&tx, game.room_id)?.unwrap();
-	let player = room.get_guest(game.position).unwrap();
+	Ok((state, round_changed))
+}
 
-	if guest.id != player.id {
-		return Err(forbidden_error("it's not your turn, please wait"));
-	}
+/// The game with its seats, the board and the results if finished
+///
+/// Hole cards are only included for their owner. If everyone left went all-in,
+/// the equities of the players after each street of the runout are included.
+#[get("/{game_id}")]
+pub async fn info(auth: Option<BearerAuth>, path: web::Path<usize>) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
 
-	execute_fold(&tx, &mut room, &mut game)?;
-	let round_changed = update_round(&tx, &room, &mut game)?;
-	if game.is_over() {
-		calc_result(&tx, &mut room, &game)?;
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	let viewer = match auth {
+		Some(auth) => {
+			Some(guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?)
+		}
+		None => None,
+	};
+
+	let mut seats = Vec::new();
+	for player in get_dealt_players(&tx, game_id)? {
+		let cards = if viewer.as_ref().is_some_and(|v| v.id == player.guest.id) {
+			get_hand(&tx, game_id, player.guest.id)?
+		} else {
+			None
+		};
+		seats.push(json!({
+			"position": player.position,
+			"guest": player.guest,
+			"stack": player.stack,
+			"hand": cards,
+		}));
 	}
+	let board = get_common(&tx, &game)?;
+	let equities = get_equities(&tx, game_id)?;
+	let (game_results, pots) = if game.is_over() {
+		(
+			Some(get_results(&tx, game_id)?),
+			Some(get_pots(&tx, game_id)?),
+		)
+	} else {
+		(None, None)
+	};
 
 	tx.commit()?;
 
-	Ok(HttpResponse::Created()
-		.json(json!({"room": room, "game": game, "round_changed": round_changed})))
+	Ok(HttpResponse::Ok().json(json!({
+		"room_id": game.room_id,
+		"game": game,
+		"seats": seats,
+		"board": board,
+		"equities": equities,
+		"results": game_results,
+		"pots": pots,
+	})))
 }
 
+/// Hole cards of the guest
+///
+/// Everyone in the room can see the hands shown in the game, other hands are
+/// only visible to their owners.
 #[get("/{game_id}/hands/{guest_id}")]
 pub async fn hand(auth: BearerAuth, path: web::Path<(usize, usize)>) -> Result<HttpResponse> {
 	let (game_id, guest_id) = path.into_inner();
@@ -98,15 +217,19 @@ pub async fn hand(auth: BearerAuth, path: web::Path<(usize, usize)>) -> Result<H
 	let mut conn = open_connection()?;
 	let tx = conn.transaction()?;
 
-	game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
 	let guest = guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
 	let request_guest =
 		guest_by_id(&tx, guest_id)?.ok_or(not_found_error("request guest not found"))?;
 
 	if guest.id != request_guest.id {
-		return Err(bad_request_error(
-			"check other's hand is not supported for now",
-		));
+		let room = room_by_id(&tx, game.room_id)?.unwrap();
+		if !room.has_guest(guest.id) {
+			return Err(forbidden_error("guest not in the room"));
+		}
+		if !get_shown_guests(&tx, game_id)?.contains(&request_guest.id) {
+			return Err(forbidden_error("hand is not shown"));
+		}
 	}
 
 	let hand = get_hand(&tx, game_id, request_guest.id)?
@@ -130,7 +253,9 @@ pub async fn flop(path: web::Path<usize>) -> Result<HttpResponse> {
 		return Err(forbidden_error("game is still before flop, please wait"));
 	}
 
-	let flop = get_flop(&tx, game_id)?.unwrap();
+	// games may end before the flop
+	let cards = get_common(&tx, &game)?;
+	let flop = cards.get(..3).ok_or(not_found_error("flop not dealt"))?;
 
 	tx.commit()?;
 
@@ -150,7 +275,9 @@ pub async fn turn(path: web::Path<usize>) -> Result<HttpResponse> {
 		return Err(forbidden_error("game is still before turn, please wait"));
 	}
 
-	let turn = get_turn(&tx, game_id)?.unwrap();
+	// games may end before the turn
+	let cards = get_common(&tx, &game)?;
+	let turn = cards.get(3).ok_or(not_found_error("turn not dealt"))?;
 
 	tx.commit()?;
 
@@ -170,7 +297,9 @@ pub async fn river(path: web::Path<usize>) -> Result<HttpResponse> {
 		return Err(forbidden_error("game is still before river, please wait"));
 	}
 
-	let river = get_river(&tx, game_id)?.unwrap();
+	// games may end before the river
+	let cards = get_common(&tx, &game)?;
+	let river = cards.get(4).ok_or(not_found_error("river not dealt"))?;
 
 	tx.commit()?;
 
@@ -206,15 +335,174 @@ pub async fn results(path: web::Path<usize>) -> Result<HttpResponse> {
 	}
 
 	let results = get_results(&tx, game_id)?;
+	let winners: Vec<_> = results
+		.iter()
+		.filter(|r| r.winner)
+		.map(|r| r.guest_id)
+		.collect();
+	let pots = get_pots(&tx, game_id)?;
 
 	tx.commit()?;
 
-	Ok(HttpResponse::Ok().json(json!({"results": results})))
+	Ok(HttpResponse::Ok().json(json!({"results": results, "winners": winners, "pots": pots})))
+}
+
+/// Legal actions of the authenticated guest, null if it's not their turn
+#[get("/{game_id}/actions")]
+pub async fn actions(auth: BearerAuth, path: web::Path<usize>) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
+
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	let guest = guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	let room = room_by_id(&tx, game.room_id)?.unwrap();
+	if !room.has_guest(guest.id) {
+		return Err(forbidden_error("guest not in the game"));
+	}
+
+	let actions = game
+		.legal_actions(&room)
+		.filter(|actions| room.get_guest(actions.position).unwrap().id == guest.id);
+
+	tx.commit()?;
+
+	Ok(HttpResponse::Ok().json(json!({"actions": actions})))
+}
+
+/// Records of the game, hole cards are only visible to their owner
+#[get("/{game_id}/records")]
+pub async fn records(
+	auth: Option<BearerAuth>,
+	path: web::Path<usize>,
+	query: web::Query<RecordsQuery>,
+) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
+
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	let guest = match auth {
+		Some(auth) => {
+			Some(guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?)
+		}
+		None => None,
+	};
+
+	let mut records = get_records(&tx, game_id, query.offset)?;
+	for record in &mut records {
+		if record.is_hole_cards() && guest.as_ref().is_none_or(|g| record.guest_id != Some(g.id)) {
+			record.cards.clear();
+		}
+	}
+
+	tx.commit()?;
+
+	Ok(HttpResponse::Ok().json(json!({"records": records})))
+}
+
+/// Hand history of a finished game in the `format`, `pokerstars` or `ohh`
+///
+/// Hole cards are only dealt to the authenticated guest, other hands are seen
+/// when they are shown.
+#[get("/{game_id}/history")]
+pub async fn history(
+	auth: Option<BearerAuth>,
+	path: web::Path<usize>,
+	query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
+	info!("get: history of game {game_id} in {}", query.format);
+
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	if !game.is_over() {
+		return Err(forbidden_error("game is not finished"));
+	}
+	let hero = match auth {
+		Some(auth) => Some(
+			guest_by_token(&tx, auth.token())?
+				.ok_or(unauthorized_error("invalid token"))?
+				.id,
+		),
+		None => None,
+	};
+
+	let history = load_history(&tx, game_id, hero)?.unwrap();
+	let response = match query.format.as_str() {
+		"pokerstars" => HttpResponse::Ok()
+			.content_type("text/plain; charset=utf-8")
+			.body(history.to_pokerstars()),
+		"ohh" => HttpResponse::Ok().json(history.to_ohh()),
+		format => return Err(bad_request_error(format!("unsupported format: {format}"))),
+	};
+
+	tx.commit()?;
+
+	Ok(response)
+}
+
+/// Replay of a finished game, with a snapshot of the table after each record
+///
+/// Hole cards are visible as in the hand history.
+#[get("/{game_id}/replay")]
+pub async fn replay(auth: Option<BearerAuth>, path: web::Path<usize>) -> Result<HttpResponse> {
+	let game_id = path.into_inner();
+	info!("get: replay of game {game_id}");
+
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	let game = game_by_id(&tx, game_id)?.ok_or(not_found_error("game not found"))?;
+	if !game.is_over() {
+		return Err(forbidden_error("game is not finished"));
+	}
+	let viewer = match auth {
+		Some(auth) => Some(
+			guest_by_token(&tx, auth.token())?
+				.ok_or(unauthorized_error("invalid token"))?
+				.id,
+		),
+		None => None,
+	};
+
+	let replay = load_history(&tx, game_id, viewer)?.unwrap().replay();
+
+	tx.commit()?;
+
+	Ok(HttpResponse::Ok().json(json!({"replay": replay})))
+}
+
+/// Import a finished game in the Open Hand History format
+///
+/// The game is imported into a new read-only room, with its players as new
+/// guests.
+#[post("/import")]
+pub async fn import(auth: BearerAuth, body: web::Json<Ohh>) -> Result<HttpResponse> {
+	info!("post: import game {}", body.ohh.game_number);
+
+	let imported = HandHistory::from_ohh(body.into_inner())?;
+
+	let mut conn = open_connection()?;
+	let tx = new_transaction(&mut conn)?;
+
+	guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
+	let game = import_history(&tx, imported)?;
+
+	tx.commit()?;
+
+	Ok(HttpResponse::Ok().json(json!({"game": game})))
 }
 
 #[must_use]
 pub fn game_api() -> actix_web::Scope {
 	web::scope("/games")
+		.service(info)
+		.service(play)
 		.service(bet)
 		.service(fold)
 		.service(hand)
@@ -223,4 +511,104 @@ pub fn game_api() -> actix_web::Scope {
 		.service(river)
 		.service(common)
 		.service(results)
+		.service(actions)
+		.service(records)
+		.service(replay)
+		.service(import)
+		.service(history)
+}
+
+#[cfg(test)]
+mod tests {
+	use actix_web::{App, http::StatusCode, test};
+	use serde_json::Value;
+
+	use super::*;
+	use crate::{
+		api::room_api,
+		db::{self, new_guest_and_token},
+	};
+
+	#[actix_web::test]
+	async fn test_game_ending_early() {
+		db::init();
+		let app = test::init_service(App::new().service(room_api()).service(game_api())).await;
+		let bearer = |token: &str| ("Authorization", format!("Bearer {token}"));
+		let get = |uri: String| test::TestRequest::get().uri(&uri).to_request();
+
+		// fold before the flop, then fold on the flop
+		for (moves, dealt) in [(&["fold"][..], 0), (&["call", "check", "fold"], 3)] {
+			let tokens: Vec<_> = ["alice", "bob"]
+				.into_iter()
+				.map(|name| new_guest_and_token(name).unwrap().1)
+				.collect();
+			let req = test::TestRequest::post()
+				.uri("/rooms")
+				.insert_header(bearer(&tokens[0]))
+				.to_request();
+			let resp: Value = test::call_and_read_body_json(&app, req).await;
+			let room_id = resp["room"]["id"].as_u64().unwrap();
+			let req = test::TestRequest::patch()
+				.uri(&format!("/rooms/{room_id}"))
+				.insert_header(bearer(&tokens[1]))
+				.to_request();
+			test::call_service(&app, req).await;
+			let mut game_id = 0;
+			for token in &tokens {
+				let req = test::TestRequest::put()
+					.uri(&format!("/rooms/{room_id}/ready"))
+					.insert_header(bearer(token))
+					.to_request();
+				let resp: Value = test::call_and_read_body_json(&app, req).await;
+				game_id = resp["game"]["id"].as_u64().unwrap_or(0);
+			}
+
+			// whoever's turn it is plays the move
+			for action in moves {
+				let mut played = false;
+				for token in &tokens {
+					let req = test::TestRequest::patch()
+						.uri(&format!("/games/{game_id}/records"))
+						.insert_header(bearer(token))
+						.set_form([("action", action)])
+						.to_request();
+					if test::call_service(&app, req).await.status().is_success() {
+						played = true;
+						break;
+					}
+				}
+				assert!(played, "{action}");
+			}
+
+			let req = test::TestRequest::get()
+				.uri(&format!("/games/{game_id}"))
+				.insert_header(bearer(&tokens[0]))
+				.to_request();
+			let resp: Value = test::call_and_read_body_json(&app, req).await;
+			assert_eq!(resp["game"]["round"], "Over");
+			assert_eq!(resp["results"].as_array().unwrap().len(), 2);
+			assert!(resp["seats"][0]["hand"].is_array());
+			assert!(resp["seats"][1]["hand"].is_null());
+			let board = resp["board"].as_array().unwrap().clone();
+			assert_eq!(board.len(), dealt);
+
+			let resp: Value =
+				test::call_and_read_body_json(&app, get(format!("/games/{game_id}/common"))).await;
+			assert_eq!(resp["cards"].as_array().unwrap(), &board);
+
+			// undealt streets are not leaked from the deck
+			let resp = test::call_service(&app, get(format!("/games/{game_id}/flop"))).await;
+			if dealt == 0 {
+				assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+			} else {
+				let resp: Value = test::read_body_json(resp).await;
+				assert_eq!(resp["flop"].as_array().unwrap(), &board);
+			}
+			for street in ["turn", "river"] {
+				let resp =
+					test::call_service(&app, get(format!("/games/{game_id}/{street}"))).await;
+				assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+			}
+		}
+	}
 }
@@ -0,0 +1,73 @@
+use std::{
+	collections::HashMap,
+	sync::{LazyLock, Mutex},
+};
+
+use tokio::sync::broadcast;
+
+use crate::engine::SeqEvent;
+
+/// Events kept for slow receivers before they start to lag
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Broadcast channel of each room being watched
+static CHANNELS: LazyLock<Mutex<HashMap<usize, broadcast::Sender<SeqEvent>>>> =
+	LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Subscribe to events of the room
+///
+/// Channels of rooms nobody watches anymore are removed first.
+#[must_use]
+pub fn subscribe(room_id: usize) -> broadcast::Receiver<SeqEvent> {
+	let mut channels = CHANNELS.lock().unwrap();
+	channels.retain(|_, sender| sender.receiver_count() > 0);
+	channels
+		.entry(room_id)
+		.or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+		.subscribe()
+}
+
+/// Push events to everyone watching the room
+///
+/// Events should be inserted into the event log by
+/// [`crate::db::insert_events`], and only published after the transaction is
+/// committed. They are dropped if nobody is watching.
+pub fn publish(room_id: usize, events: Vec<SeqEvent>) {
+	let mut channels = CHANNELS.lock().unwrap();
+	if let Some(sender) = channels.get(&room_id) {
+		if sender.receiver_count() == 0 {
+			channels.remove(&room_id);
+			return;
+		}
+		for event in events {
+			// receivers may be dropped meanwhile
+			let _ = sender.send(event);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn is_watched(room_id: usize) -> bool {
+		CHANNELS.lock().unwrap().contains_key(&room_id)
+	}
+
+	#[test]
+	fn test_unwatched_removed() {
+		// room ids no other test uses
+		let (first, second) = (usize::MAX, usize::MAX - 1);
+
+		let receiver = subscribe(first);
+		assert!(is_watched(first));
+		drop(receiver);
+		publish(first, Vec::new());
+		assert!(!is_watched(first));
+
+		drop(subscribe(first));
+		let _receiver = subscribe(second);
+		assert!(!is_watched(first));
+		assert!(is_watched(second));
+	}
+}
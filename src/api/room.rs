@@ -1,22 +1,79 @@
-use actix_web::{HttpResponse, delete, get, patch, post, put, web};
+use std::time::Duration;
+
+use actix_web::{
+	HttpRequest, HttpResponse, delete, get,
+	http::header::{ETAG, IF_NONE_MATCH},
+	patch, post, put,
+	rt::time::{Instant, timeout},
+	web,
+};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
-use serde_json::json;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::broadcast::error::RecvError;
 use tracing::info;
 
+use super::{
+	hub::{publish, subscribe},
+	sse::sse,
+	ws::ws,
+};
 use crate::{
+	RoomSettings,
 	db::{
-		commit, execute, get_games, get_running_game, guest_by_token, new_game, new_room,
-		new_transaction, open_connection, room_by_id,
+		GameFilter, bump_version, commit, count_games, execute, find_games, game_start, get_common,
+		get_games, get_players, get_pots, get_results, get_running_game, guest_by_id,
+		guest_by_token, insert_events, is_imported, last_event_seq, new_game, new_room,
+		new_transaction, open_connection, room_by_id, room_version,
 	},
+	engine::RoomEvent,
 	error::{
-		Result, conflict_error, forbidden_error, internal_server_error, not_found_error,
-		unauthorized_error,
+		Result, bad_request_error, conflict_error, forbidden_error, internal_server_error,
+		not_found_error, unauthorized_error,
 	},
 };
 
+/// Seconds to hold a long poll by default
+const DEFAULT_WAIT: u64 = 30;
+/// Max seconds to hold a long poll
+const MAX_WAIT: u64 = 60;
+
+/// Games listed by default
+const DEFAULT_LIMIT: usize = 20;
+/// Max games listed at once
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct GamesQuery {
+	limit: Option<usize>,
+	#[serde(default)]
+	offset: usize,
+	/// `asc` for the oldest first, `desc` for the newest first
+	order: Option<String>,
+	/// Started at or after this UTC time
+	from: Option<String>,
+	/// Started before this UTC time
+	to: Option<String>,
+	/// Dealt to this guest
+	guest_id: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotQuery {
+	/// Hold the request until the version of the room is greater than this
+	since: Option<usize>,
+	/// Seconds to hold the request at most
+	wait: Option<u64>,
+}
+
 /// Create a new room
+///
+/// Settings are optional, the defaults are used without a body.
 #[post("")]
-pub async fn new(auth: BearerAuth) -> actix_web::Result<HttpResponse> {
+pub async fn new(
+	auth: BearerAuth,
+	form: Option<web::Form<RoomSettings>>,
+) -> actix_web::Result<HttpResponse> {
 	info!("post: create a new room");
 
 	let mut conn = open_connection()?;
@@ -24,7 +81,8 @@ pub async fn new(auth: BearerAuth) -> actix_web::Result<HttpResponse> {
 
 	let guest = guest_by_token(&tx, auth.token())?;
 	let guest = guest.ok_or(unauthorized_error("invalid token"))?;
-	let room = new_room(&tx, &guest)?;
+	let settings = form.map(web::Form::into_inner).unwrap_or_default();
+	let room = new_room(&tx, &guest, settings)?;
 
 	commit(tx)?;
 
@@ -42,6 +100,9 @@ pub async fn join(auth: BearerAuth, path: web::Path<usize>) -> Result<HttpRespon
 
 	let guest = guest_by_token(&tx, auth.token())?.ok_or(unauthorized_error("invalid token"))?;
 	let mut room = room_by_id(&tx, room_id)?.ok_or(not_found_error("room not found"))?;
+	if is_imported(&tx, room_id)? {
+		return Err(forbidden_error("imported rooms are read-only"));
+	}
 	let position = room
 		.insert(guest.clone())
 		.ok_or(forbidden_error("room full or already in"))?;
@@ -53,31 +114,106 @@ pub async fn join(auth: BearerAuth, path: web::Path<usize>) -> Result<HttpRespon
 
 	execute(
 		&tx,
-		"insert into seat(room_id, position, guest_id) values(?1, ?2, ?3)",
-		(room_id, position, guest.id),
+		"insert into seat(room_id, position, guest_id, stack) values(?1, ?2, ?3, ?4)",
+		(room_id, position, guest.id, room.settings.starting_stack),
 	)?;
+	bump_version(&tx, room_id)?;
 
+	let events = insert_events(
+		&tx,
+		room_id,
+		vec![RoomEvent::SeatJoined { position, guest }],
+	)?;
 	commit(tx)?;
+	publish(room_id, events);
 
 	Ok(HttpResponse::Ok().json(json!({"room": room})))
 }
 
 /// Get the room and the last game
+///
+/// With `since`, the request is held until the version of the room is greater
+/// than it or `wait` seconds passed. The version is sent as the ETag, so a
+/// request with a matching `If-None-Match` gets 304 if nothing changed.
 #[get("/{room_id}")]
-pub async fn get_room(path: web::Path<usize>) -> Result<HttpResponse> {
+pub async fn get_room(
+	req: HttpRequest,
+	path: web::Path<usize>,
+	query: web::Query<SnapshotQuery>,
+) -> Result<HttpResponse> {
 	let room_id = path.into_inner();
+	let wait = Duration::from_secs(query.wait.unwrap_or(DEFAULT_WAIT).min(MAX_WAIT));
+	let deadline = Instant::now() + wait;
+
+	// the room must exist before anyone subscribes to it
+	let (mut version, mut snapshot) = room_snapshot(room_id)?;
+	if let Some(since) = query.since
+		&& version <= since
+	{
+		// subscribe and read the version again so that no change is missed
+		let mut receiver = subscribe(room_id);
+		loop {
+			(version, snapshot) = room_snapshot(room_id)?;
+			if version > since {
+				break;
+			}
+			// every change is published after it is committed
+			match timeout(
+				deadline.saturating_duration_since(Instant::now()),
+				receiver.recv(),
+			)
+			.await
+			{
+				Ok(Ok(_) | Err(RecvError::Lagged(_))) => (),
+				Ok(Err(RecvError::Closed)) | Err(_) => {
+					(version, snapshot) = room_snapshot(room_id)?;
+					break;
+				}
+			}
+		}
+	}
+
+	let etag = format!("\"{version}\"");
+	let matched = req
+		.headers()
+		.get(IF_NONE_MATCH)
+		.and_then(|value| value.to_str().ok())
+		.is_some_and(|value| {
+			value
+				.split(',')
+				.map(str::trim)
+				.any(|tag| tag == "*" || tag == etag)
+		});
+	if matched {
+		return Ok(HttpResponse::NotModified()
+			.insert_header((ETAG, etag))
+			.finish());
+	}
 
+	Ok(HttpResponse::Ok()
+		.insert_header((ETAG, etag))
+		.json(snapshot))
+}
+
+/// The version and the JSON of the room with its last game
+fn room_snapshot(room_id: usize) -> Result<(usize, Value)> {
 	let mut conn = open_connection()?;
 	let tx = new_transaction(&mut conn)?;
 
 	let room = room_by_id(&tx, room_id)?.ok_or(not_found_error("room not found"))?;
+	let version = room_version(&tx, room_id)?.unwrap();
 
 	let games = get_games(&tx, room_id, false, 1, 0)?;
 	let game = games.first();
+	// resume the event stream of the room from this snapshot
+	let last_event_id = last_event_seq(&tx, room_id)?;
 
 	commit(tx)?;
 
-	Ok(HttpResponse::Ok().json(json!({"room": room, "game": game})))
+	Ok((
+		version,
+		json!({"room": room, "game": game, "version": version, "last_event_id": last_event_id}),
+	))
 }
 
 /// Set the guest to be ready
@@ -100,7 +236,13 @@ pub async fn ready(auth: BearerAuth, path: web::Path<usize>) -> actix_web::Resul
 	if room.is_ready(guest.id).unwrap() {
 		return Err(conflict_error("guest is already ready").into());
 	}
-	room.ready(guest.id).unwrap();
+	if room.ready(guest.id).is_none() {
+		return Err(forbidden_error(format!(
+			"stack is less than the min stack {} of the room",
+			room.settings.min_stack
+		))
+		.into());
+	}
 
 	if execute(
 		&tx,
@@ -110,13 +252,29 @@ pub async fn ready(auth: BearerAuth, path: web::Path<usize>) -> actix_web::Resul
 	{
 		return Err(internal_server_error("failed to set ready, please retry").into());
 	}
+	bump_version(&tx, room_id)?;
+
+	let mut events = vec![RoomEvent::ReadyChanged {
+		guest_id: guest.id,
+		ready: true,
+	}];
+	let (room, game) = if room.should_start() {
+		let (state, game_events) = new_game(&tx, room)?;
+		events.push(RoomEvent::HandStarted {
+			game_id: state.game.id,
+			button: state.room.button,
+			sb: state.room.sb,
+			bb: state.room.bb,
+		});
+		events.extend(RoomEvent::from_game(&state, &game_events));
+		(state.room, Some(state.game))
+	} else {
+		(room, None)
+	};
 
-	let mut game = None;
-	if room.should_start() {
-		game = Some(new_game(&tx, &mut room)?);
-	}
-
+	let events = insert_events(&tx, room_id, events)?;
 	commit(tx)?;
+	publish(room_id, events);
 
 	Ok(HttpResponse::Ok().json(json!({"room": room, "game": game})))
 }
@@ -152,8 +310,18 @@ pub async fn unready(auth: BearerAuth, path: web::Path<usize>) -> actix_web::Res
 	{
 		return Err(internal_server_error("failed to set unready, please retry").into());
 	}
+	bump_version(&tx, room_id)?;
 
+	let events = insert_events(
+		&tx,
+		room_id,
+		vec![RoomEvent::ReadyChanged {
+			guest_id: guest.id,
+			ready: false,
+		}],
+	)?;
 	commit(tx)?;
+	publish(room_id, events);
 
 	Ok(HttpResponse::Ok().json(json!({"room": room})))
 }
@@ -175,6 +343,86 @@ pub async fn current_game(path: web::Path<usize>) -> actix_web::Result<HttpRespo
 	Ok(HttpResponse::Ok().json(json!({"game": game})))
 }
 
+/// Games played in the room with their summaries, the newest first by default
+#[get("/{room_id}/games")]
+pub async fn game_history(
+	path: web::Path<usize>,
+	query: web::Query<GamesQuery>,
+) -> Result<HttpResponse> {
+	let room_id = path.into_inner();
+	info!("get: games of room {room_id}");
+
+	let asc = match query.order.as_deref() {
+		Some("asc") => true,
+		Some("desc") | None => false,
+		Some(order) => return Err(bad_request_error(format!("unknown order: {order}"))),
+	};
+	let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+	let filter = GameFilter {
+		from: query.from.as_deref().map(parse_time).transpose()?,
+		to: query.to.as_deref().map(parse_time).transpose()?,
+		guest_id: query.guest_id,
+	};
+
+	let mut conn = open_connection()?;
+	let tx = new_transaction(&mut conn)?;
+
+	room_by_id(&tx, room_id)?.ok_or(not_found_error("room not found"))?;
+	let total = count_games(&tx, room_id, &filter)?;
+
+	let mut summaries = Vec::new();
+	for game in find_games(&tx, room_id, &filter, asc, limit, query.offset)? {
+		let (button, started_at) = game_start(&tx, game.id)?;
+		let pots = get_pots(&tx, game.id)?;
+		let mut winners = Vec::new();
+		for result in get_results(&tx, game.id)?.iter().filter(|r| r.winner) {
+			let won: usize = pots
+				.iter()
+				.flat_map(|pot| &pot.winners)
+				.filter(|&&(id, _)| id == result.guest_id)
+				.map(|&(_, won)| won)
+				.sum();
+			let name = guest_by_id(&tx, result.guest_id)?.unwrap().name;
+			winners.push(json!({"guest_id": result.guest_id, "name": name, "won": won}));
+		}
+		summaries.push(json!({
+			"board": get_common(&tx, &game)?,
+			"players": get_players(&tx, game.id)?,
+			"winners": winners,
+			"button": button,
+			"started_at": started_at,
+			"game": game,
+		}));
+	}
+
+	commit(tx)?;
+
+	Ok(HttpResponse::Ok().json(json!({
+		"games": summaries,
+		"total": total,
+		"limit": limit,
+		"offset": query.offset,
+	})))
+}
+
+/// Normalize a UTC time like `2025-01-31T18:30:00Z` or `2025-01-31` to the
+/// format of the database
+fn parse_time(time: &str) -> Result<String> {
+	let time = time.strip_suffix('Z').unwrap_or(time).replacen('T', " ", 1);
+	let valid = matches!(time.len(), 10 | 16 | 19)
+		&& time.char_indices().all(|(i, c)| match i {
+			4 | 7 => c == '-',
+			10 => c == ' ',
+			13 | 16 => c == ':',
+			_ => c.is_ascii_digit(),
+		});
+	if !valid {
+		return Err(bad_request_error(format!("invalid time: {time}")));
+	}
+
+	Ok(time)
+}
+
 #[must_use]
 pub fn room_api() -> actix_web::Scope {
 	web::scope("/rooms")
@@ -183,5 +431,192 @@ pub fn room_api() -> actix_web::Scope {
 		.service(ready)
 		.service(unready)
 		.service(current_game)
+		.service(game_history)
 		.service(get_room)
+		.service(ws)
+		.service(sse)
+}
+
+#[cfg(test)]
+mod tests {
+	use actix_web::{App, http::StatusCode, test};
+
+	use super::*;
+	use crate::{
+		api::game_api,
+		db::{self, new_guest_and_token},
+	};
+
+	fn bearer(token: &str) -> (&'static str, String) {
+		("Authorization", format!("Bearer {token}"))
+	}
+
+	fn version(room_id: usize) -> usize {
+		let mut conn = open_connection().unwrap();
+		let tx = conn.transaction().unwrap();
+		room_version(&tx, room_id).unwrap().unwrap()
+	}
+
+	#[actix_web::test]
+	async fn test_new_without_body() {
+		db::init();
+		let (_, token) = new_guest_and_token("alice").unwrap();
+		let app = test::init_service(App::new().service(room_api())).await;
+
+		let req = test::TestRequest::post()
+			.uri("/rooms")
+			.insert_header(("Authorization", format!("Bearer {token}")))
+			.to_request();
+		let resp: Value = test::call_and_read_body_json(&app, req).await;
+		assert_eq!(
+			resp["room"]["settings"],
+			serde_json::to_value(RoomSettings::default()).unwrap()
+		);
+	}
+
+	#[actix_web::test]
+	async fn test_version_bumped() {
+		db::init();
+		let (_, alice_token) = new_guest_and_token("alice").unwrap();
+		let (_, bob_token) = new_guest_and_token("bob").unwrap();
+		let app = test::init_service(App::new().service(room_api()).service(game_api())).await;
+
+		let req = test::TestRequest::post()
+			.uri("/rooms")
+			.insert_header(bearer(&alice_token))
+			.to_request();
+		let resp: Value = test::call_and_read_body_json(&app, req).await;
+		let room_id = resp["room"]["id"].as_u64().unwrap() as usize;
+		let mut last = version(room_id);
+
+		let changes = [
+			test::TestRequest::patch().insert_header(bearer(&bob_token)),
+			test::TestRequest::put().insert_header(bearer(&alice_token)),
+			test::TestRequest::delete().insert_header(bearer(&alice_token)),
+			test::TestRequest::put().insert_header(bearer(&alice_token)),
+			test::TestRequest::put().insert_header(bearer(&bob_token)),
+		];
+		let mut game = Value::Null;
+		for (i, req) in changes.into_iter().enumerate() {
+			let uri = if i == 0 {
+				format!("/rooms/{room_id}")
+			} else {
+				format!("/rooms/{room_id}/ready")
+			};
+			let resp: Value = test::call_and_read_body_json(&app, req.uri(&uri).to_request()).await;
+			game = resp["game"].clone();
+			let current = version(room_id);
+			assert!(current > last, "change {i}");
+			last = current;
+		}
+
+		// both are ready, play the game by calling, betting and checking
+		let game_id = game["id"].as_u64().unwrap();
+		let mut over = false;
+		while !over {
+			let mut played = false;
+			for token in [&alice_token, &bob_token] {
+				for form in [
+					vec![("action", "call")],
+					vec![("action", "bet"), ("amount", "2")],
+					vec![("action", "check")],
+				] {
+					let req = test::TestRequest::patch()
+						.uri(&format!("/games/{game_id}/records"))
+						.insert_header(bearer(token))
+						.set_form(form)
+						.to_request();
+					let resp = test::call_service(&app, req).await;
+					if resp.status().is_success() {
+						let resp: Value = test::read_body_json(resp).await;
+						over = resp["game"]["round"] == "Over";
+						played = true;
+						break;
+					}
+				}
+				if played {
+					break;
+				}
+			}
+			assert!(played);
+			let current = version(room_id);
+			assert_eq!(current, last + 1);
+			last = current;
+		}
+
+		let etag = format!("\"{last}\"");
+		let req = test::TestRequest::get()
+			.uri(&format!("/rooms/{room_id}"))
+			.insert_header((IF_NONE_MATCH, etag.clone()))
+			.to_request();
+		let resp = test::call_service(&app, req).await;
+		assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+		assert_eq!(resp.headers().get(ETAG).unwrap().to_str().unwrap(), etag);
+
+		let req = test::TestRequest::get()
+			.uri(&format!("/rooms/{room_id}"))
+			.insert_header((IF_NONE_MATCH, format!("\"{}\"", last - 1)))
+			.to_request();
+		let resp = test::call_service(&app, req).await;
+		assert_eq!(resp.status(), StatusCode::OK);
+	}
+
+	#[actix_web::test]
+	async fn test_game_history_query() {
+		db::init();
+		let (_, token) = new_guest_and_token("alice").unwrap();
+		let app = test::init_service(App::new().service(room_api())).await;
+		let req = test::TestRequest::post()
+			.uri("/rooms")
+			.insert_header(bearer(&token))
+			.to_request();
+		let resp: Value = test::call_and_read_body_json(&app, req).await;
+		let room_id = resp["room"]["id"].as_u64().unwrap();
+
+		for (query, limit) in [("", 20), ("?limit=1000&offset=5", 100), ("?limit=3", 3)] {
+			let req = test::TestRequest::get()
+				.uri(&format!("/rooms/{room_id}/games{query}"))
+				.to_request();
+			let resp: Value = test::call_and_read_body_json(&app, req).await;
+			assert_eq!(resp["limit"], limit);
+			assert_eq!(resp["total"], 0);
+		}
+		let req = test::TestRequest::get()
+			.uri(&format!("/rooms/{room_id}/games?offset=5"))
+			.to_request();
+		let resp: Value = test::call_and_read_body_json(&app, req).await;
+		assert_eq!(resp["offset"], 5);
+
+		for query in ["order=sideways", "from=yesterday", "to=2025-1-31"] {
+			let req = test::TestRequest::get()
+				.uri(&format!("/rooms/{room_id}/games?{query}"))
+				.to_request();
+			let resp = test::call_service(&app, req).await;
+			assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{query}");
+		}
+	}
+
+	#[actix_web::test]
+	async fn test_parse_time() {
+		assert_eq!(
+			parse_time("2025-01-31T18:30:00Z").unwrap(),
+			"2025-01-31 18:30:00"
+		);
+		assert_eq!(
+			parse_time("2025-01-31 18:30:00").unwrap(),
+			"2025-01-31 18:30:00"
+		);
+		assert_eq!(parse_time("2025-01-31T18:30").unwrap(), "2025-01-31 18:30");
+		assert_eq!(parse_time("2025-01-31").unwrap(), "2025-01-31");
+		for time in [
+			"2025-1-31",
+			"2025-01-31T18",
+			"2025-01-31T18:30:00+02:00",
+			"2025/01/31",
+			"yesterday",
+			"",
+		] {
+			assert!(parse_time(time).is_err(), "{time}");
+		}
+	}
 }
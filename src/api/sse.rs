@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use actix_web::{HttpRequest, HttpResponse, get, web, web::Bytes};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use futures_util::stream;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+use tracing::info;
+
+use super::hub::subscribe;
+use crate::{
+	db::{get_events, guest_by_token, last_event_seq, open_connection, room_by_id},
+	engine::SeqEvent,
+	error::{Result, bad_request_error, not_found_error, unauthorized_error},
+};
+
+/// State of an event stream of a room
+struct Feed {
+	room_id: usize,
+	guest_id: Option<usize>,
+	receiver: Receiver<SeqEvent>,
+	/// Events to send before receiving new ones
+	pending: VecDeque<SeqEvent>,
+	/// Sequence number of the last event handled, sent or not
+	last: usize,
+}
+
+impl Feed {
+	/// The next event visible to the guest, None if the stream should end
+	async fn next(&mut self) -> Option<SeqEvent> {
+		loop {
+			if let Some(event) = self.pending.pop_front() {
+				return Some(event);
+			}
+
+			let event = match self.receiver.recv().await {
+				Ok(event) => event,
+				// the client can resume with the last event ID
+				Err(RecvError::Lagged(_) | RecvError::Closed) => return None,
+			};
+			if event.seq <= self.last {
+				continue;
+			}
+			if event.seq > self.last + 1 {
+				// events may be published out of order, fill the gap from the log
+				let mut conn = open_connection().ok()?;
+				let tx = conn.transaction().ok()?;
+				let events = get_events(&tx, self.room_id, self.last, self.guest_id).ok()?;
+				self.last = events.last().map_or(event.seq, |e| e.seq.max(event.seq));
+				self.pending.extend(events);
+				continue;
+			}
+
+			self.last = event.seq;
+			if event.event.visible_to(self.guest_id) {
+				return Some(event);
+			}
+		}
+	}
+}
+
+/// Format the event as a server-sent event
+fn to_sse(event: &SeqEvent) -> Bytes {
+	let data = serde_json::to_value(event).unwrap();
+	Bytes::from(format!(
+		"id: {}\nevent: {}\ndata: {data}\n\n",
+		event.seq,
+		data["type"].as_str().unwrap()
+	))
+}
+
+/// Server-sent events of the room
+///
+/// Event IDs are sequence numbers of the room's event log. A client sending
+/// the `Last-Event-ID` header gets every event after it first, otherwise
+/// only new events are sent. Hole cards are only sent to their owner.
+#[get("/{room_id}/events")]
+pub async fn sse(
+	req: HttpRequest,
+	auth: Option<BearerAuth>,
+	path: web::Path<usize>,
+) -> Result<HttpResponse> {
+	let room_id = path.into_inner();
+	info!("get: events of room {room_id}");
+
+	let last_event_id = match req.headers().get("Last-Event-ID") {
+		Some(value) => Some(
+			value
+				.to_str()
+				.ok()
+				.and_then(|v| v.trim().parse::<usize>().ok())
+				.ok_or(bad_request_error("invalid Last-Event-ID"))?,
+		),
+		None => None,
+	};
+
+	let guest_id = {
+		let mut conn = open_connection()?;
+		let tx = conn.transaction()?;
+		room_by_id(&tx, room_id)?.ok_or(not_found_error("room not found"))?;
+		match auth {
+			Some(auth) => Some(
+				guest_by_token(&tx, auth.token())?
+					.ok_or(unauthorized_error("invalid token"))?
+					.id,
+			),
+			None => None,
+		}
+	};
+
+	// subscribe before reading the log so that no event is missed
+	let receiver = subscribe(room_id);
+
+	let mut conn = open_connection()?;
+	let tx = conn.transaction()?;
+
+	let (pending, last) = match last_event_id {
+		Some(last) => {
+			let events = get_events(&tx, room_id, last, guest_id)?;
+			let last = last_event_seq(&tx, room_id)?.max(last);
+			(events.into(), last)
+		}
+		None => (VecDeque::new(), last_event_seq(&tx, room_id)?),
+	};
+
+	tx.commit()?;
+
+	let feed = Feed {
+		room_id,
+		guest_id,
+		receiver,
+		pending,
+		last,
+	};
+	let body = stream::unfold(feed, |mut feed| async move {
+		let event = feed.next().await?;
+		Some((Ok::<_, actix_web::Error>(to_sse(&event)), feed))
+	});
+
+	Ok(HttpResponse::Ok()
+		.content_type("text/event-stream")
+		.insert_header(("Cache-Control", "no-cache"))
+		.streaming(body))
+}
@@ -0,0 +1,76 @@
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use actix_ws::Message;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use super::hub::subscribe;
+use crate::{
+	db::{guest_by_token, open_connection, room_by_id},
+	error::{Result, internal_server_error, not_found_error, unauthorized_error},
+};
+
+/// Watch events of the room
+///
+/// Every connection gets its own view, hole cards are only sent to their
+/// owner, so authenticate to receive yours.
+#[get("/{room_id}/ws")]
+pub async fn ws(
+	req: HttpRequest,
+	body: web::Payload,
+	auth: Option<BearerAuth>,
+	path: web::Path<usize>,
+) -> Result<HttpResponse> {
+	let room_id = path.into_inner();
+	info!("get: watch room {room_id}");
+
+	let guest_id = {
+		let mut conn = open_connection()?;
+		let tx = conn.transaction()?;
+		room_by_id(&tx, room_id)?.ok_or(not_found_error("room not found"))?;
+		match auth {
+			Some(auth) => Some(
+				guest_by_token(&tx, auth.token())?
+					.ok_or(unauthorized_error("invalid token"))?
+					.id,
+			),
+			None => None,
+		}
+	};
+
+	let (response, mut session, mut stream) =
+		actix_ws::handle(&req, body).map_err(|err| internal_server_error(err.to_string()))?;
+	let mut receiver = subscribe(room_id);
+
+	actix_web::rt::spawn(async move {
+		loop {
+			tokio::select! {
+				event = receiver.recv() => match event {
+					Ok(event) => {
+						if !event.event.visible_to(guest_id) {
+							continue;
+						}
+						let text = serde_json::to_string(&event).unwrap();
+						if session.text(text).await.is_err() {
+							return;
+						}
+					}
+					// events are lost, let the client reconnect and resync
+					Err(RecvError::Lagged(_) | RecvError::Closed) => break,
+				},
+				message = stream.recv() => match message {
+					Some(Ok(Message::Ping(bytes))) => {
+						if session.pong(&bytes).await.is_err() {
+							return;
+						}
+					}
+					Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+					Some(Ok(_)) => (),
+				},
+			}
+		}
+		let _ = session.close(None).await;
+	});
+
+	Ok(response)
+}
@@ -1,5 +1,6 @@
 pub mod core;
 pub mod error;
+pub mod event;
 pub mod game;
 pub mod guest;
 pub mod room;
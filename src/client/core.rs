@@ -1,11 +1,10 @@
-use std::{fmt::Display, io::Write, process::exit, time::Duration};
+use std::{fmt::Display, io::Write, process::exit};
 
-use actix_web::rt::time::sleep;
 use serde::Deserialize;
 
-use crate::{Card, Game, Guest, Room, sprintln};
+use crate::{Card, Game, Guest, Room, RoomSettings, sprintln};
 
-use super::{error::anyhow_error, game::RoomResponse};
+use super::{error::anyhow_error, event::EventStream, game::RoomResponse};
 
 #[derive(Debug, Deserialize)]
 pub struct ErrorResponse {
@@ -27,6 +26,7 @@ pub struct Client {
 	pub game: Option<Game>,
 	pub hand: Vec<Card>,
 	pub common: Vec<Card>,
+	pub events: Option<EventStream>,
 }
 
 impl Default for Client {
@@ -40,14 +40,12 @@ impl Default for Client {
 			game: None,
 			hand: vec![],
 			common: vec![],
+			events: None,
 		}
 	}
 }
 
 impl Client {
-	pub const TPS: f32 = 20.0;
-	pub const TICK: f32 = 1.0 / Self::TPS;
-
 	/// Read input into an array of strings
 	pub fn read_command() -> anyhow::Result<Vec<String>> {
 		let mut input = String::new();
@@ -111,7 +109,15 @@ impl Client {
 				self.login(name).await?;
 			}
 			["new"] => {
-				self.new_room().await?;
+				self.new_room(&RoomSettings::default()).await?;
+			}
+			["new", small_blind, big_blind] => {
+				let settings = RoomSettings {
+					small_blind: small_blind.parse()?,
+					big_blind: big_blind.parse()?,
+					..RoomSettings::default()
+				};
+				self.new_room(&settings).await?;
 			}
 			["join", id] => {
 				self.join(id).await?;
@@ -135,6 +141,16 @@ impl Client {
 				sprintln!("game is over");
 				self.print_game_result().await?;
 			}
+			["show"] => {
+				if self.game.as_ref().is_some_and(Game::is_over) {
+					self.act("show", None).await?;
+				} else {
+					sprintln!("no finished game to show");
+				}
+			}
+			["replay", game_id] => {
+				self.replay(game_id.parse()?).await?;
+			}
 			["exit"] => {
 				exit(0);
 			}
@@ -144,11 +160,6 @@ impl Client {
 		Ok(())
 	}
 
-	/// Sleep for a tick
-	pub async fn tick() {
-		sleep(Duration::from_secs_f32(Self::TICK)).await;
-	}
-
 	fn print_status(&self) {
 		if self.guest.is_none() {
 			println!("not login");
@@ -190,9 +201,11 @@ fn print_help() {
 		help
 		status
 		login <name>
-		new
+		new [<small_blind> <big_blind>]
 		join <room_id>
 		ready
+		show
+		replay <game_id>
 		exit"
 	);
 }
@@ -0,0 +1,62 @@
+use actix_codec::Framed;
+use anyhow::anyhow;
+use awc::{
+	BoxedSocket,
+	ws::{Codec, Frame, Message},
+};
+use futures_util::{SinkExt, StreamExt};
+
+use crate::engine::{RoomEvent, SeqEvent};
+
+use super::{Client, error::anyhow_error};
+
+/// WebSocket connection to the events of a room
+pub type EventStream = Framed<BoxedSocket, Codec>;
+
+impl Client {
+	/// Watch events of the current room, replacing the last connection
+	pub async fn connect_events(&mut self) -> anyhow::Result<()> {
+		let room_id = self.room.as_ref().unwrap().id;
+		let (_, stream) = self
+			.awc
+			.ws(format!("{}/rooms/{room_id}/ws", self.server_addr))
+			.bearer_auth(self.token.as_ref().unwrap())
+			.connect()
+			.await
+			.map_err(anyhow_error)?;
+		self.events = Some(stream);
+
+		Ok(())
+	}
+
+	/// Wait for the next event of the current room
+	pub async fn next_event(&mut self) -> anyhow::Result<RoomEvent> {
+		let stream = self
+			.events
+			.as_mut()
+			.ok_or(anyhow!("not watching any room"))?;
+
+		loop {
+			let frame = stream
+				.next()
+				.await
+				.ok_or(anyhow!("event stream closed"))?
+				.map_err(anyhow_error)?;
+			match frame {
+				Frame::Text(text) => {
+					let event: SeqEvent = serde_json::from_slice(&text)?;
+					return Ok(event.event);
+				}
+				Frame::Ping(bytes) => stream
+					.send(Message::Pong(bytes))
+					.await
+					.map_err(anyhow_error)?,
+				Frame::Close(_) => {
+					self.events = None;
+					return Err(anyhow!("event stream closed"));
+				}
+				_ => (),
+			}
+		}
+	}
+}
@@ -1,9 +1,14 @@
 use std::process::exit;
 
 use serde::Deserialize;
-use serde_json::json;
 
-use crate::{Card, Game, GameResult, Room, client::ErrorResponse, sprintln};
+use crate::{
+	Card, Game, GameResult, Room,
+	client::ErrorResponse,
+	engine::RoomEvent,
+	history::replay::{Replay, Snapshot},
+	sprintln,
+};
 
 use super::{Client, error::anyhow_error};
 
@@ -28,44 +33,71 @@ struct ResultsResponse {
 	results: Vec<GameResult>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ReplayResponse {
+	replay: Replay,
+}
+
 impl Client {
-	/// Wait for self's turn or game over
-	pub async fn wait_turn(&mut self) -> anyhow::Result<()> {
+	/// Whether the current game is over or it's self's turn
+	fn should_act(&self) -> bool {
 		let guest_id = self.guest.as_ref().unwrap().id;
-		let room_id = self.room.as_ref().unwrap().id;
+		let room = self.room.as_ref().unwrap();
+		let game = self.game.as_ref().unwrap();
 
-		loop {
-			let mut response = self
-				.awc
-				.get(format!("{}/rooms/{}", self.server_addr, room_id))
-				.send()
-				.await
-				.map_err(anyhow_error)?;
+		game.is_over()
+			|| room.seats[game.position]
+				.as_ref()
+				.is_some_and(|s| s.guest.id == guest_id)
+	}
 
-			if response.status().is_success() {
-				let resp: RoomResponse = response.json().await?;
-				assert!(resp.game.is_some());
-				self.game = resp.game;
-				let game = self.game.as_ref().unwrap();
-				let mut should_break = game.is_over();
-				// it's my turn now
-				if resp.room.seats[game.position]
-					.as_ref()
-					.is_some_and(|s| s.guest.id == guest_id)
-				{
-					should_break = true;
-				}
+	/// Wait for self's turn or game over
+	pub async fn wait_turn(&mut self) -> anyhow::Result<()> {
+		let game_id = self.game.as_ref().unwrap().id;
 
-				self.room = Some(resp.room);
-				if should_break {
-					break;
+		while !self.should_act() {
+			match self.next_event().await? {
+				RoomEvent::ActionTaken { record } if record.game_id == game_id => {
+					let room = self.room.as_ref().unwrap();
+					let name = record
+						.position
+						.and_then(|p| room.get_guest(p))
+						.map_or("?", |g| g.name.as_str());
+					if record.cards.is_empty() {
+						sprintln!("{name}: {} {}", record.action, record.chips);
+					} else {
+						sprintln!(
+							"{name}: {} {}",
+							record.action,
+							Self::pretty_cards(&record.cards)
+						);
+					}
 				}
-			} else {
-				let resp: ErrorResponse = response.json().await?;
-				sprintln!("failed to retrive game info: {}", resp);
+				RoomEvent::Equity {
+					game_id: id,
+					round,
+					players,
+				} if id == game_id => {
+					let room = self.room.as_ref().unwrap();
+					for player in players {
+						let name = room
+							.get_guest(player.position)
+							.map_or("?", |g| g.name.as_str());
+						sprintln!(
+							"{name}: {round} equity {:.1}% (win {:.1}%, tie {:.1}%)",
+							player.equity.equity,
+							player.equity.win,
+							player.equity.tie
+						);
+					}
+				}
+				RoomEvent::Turn { game_id: id, .. } | RoomEvent::Results { game_id: id, .. }
+					if id == game_id =>
+				{
+					self.sync().await?;
+				}
+				_ => (),
 			}
-
-			Self::tick().await;
 		}
 
 		Ok(())
@@ -77,44 +109,47 @@ impl Client {
 	///
 	/// After calling this, self.game should always be some
 	pub async fn wait_game(&mut self) -> anyhow::Result<()> {
-		let room_id = self.room.as_ref().unwrap().id;
-		loop {
-			let mut room_resp = self
+		self.sync().await?;
+		if let Some(game) = &self.game
+			&& !game.is_over()
+		{
+			let guest = self.guest.as_ref().unwrap();
+			let mut hand_resp = self
 				.awc
-				.get(format!("{}/rooms/{}", self.server_addr, room_id))
+				.get(format!(
+					"{}/games/{}/hands/{}",
+					self.server_addr, game.id, guest.id
+				))
+				.bearer_auth(self.token.as_ref().unwrap())
 				.send()
 				.await
 				.map_err(anyhow_error)?;
-
-			if room_resp.status().is_success() {
-				let resp: RoomResponse = room_resp.json().await?;
-				self.room = Some(resp.room);
-				self.game = resp.game;
-				if let Some(game) = &self.game {
-					let guest = self.guest.as_ref().unwrap();
-					let mut hand_resp = self
-						.awc
-						.get(format!(
-							"{}/games/{}/hands/{}",
-							self.server_addr, game.id, guest.id
-						))
-						.bearer_auth(self.token.as_ref().unwrap())
-						.send()
-						.await
-						.map_err(anyhow_error)?;
-					if !game.is_over() && hand_resp.status().is_success() {
-						let resp: HandResponse = hand_resp.json().await?;
-						self.hand = resp.hand;
-						break;
-					}
-				}
-			} else {
-				let resp: ErrorResponse = room_resp.json().await?;
-				sprintln!("failed to retrive game info: {resp}");
+			if hand_resp.status().is_success() {
+				let resp: HandResponse = hand_resp.json().await?;
+				self.hand = resp.hand;
+				return Ok(());
 			}
+			let resp: ErrorResponse = hand_resp.json().await?;
+			sprintln!("failed to retrive game info: {resp}");
+		}
 
-			Self::tick().await;
+		let guest_id = self.guest.as_ref().unwrap().id;
+		// hole cards of the game already running are stale
+		let last_id = self.game.as_ref().map_or(0, |g| g.id);
+		loop {
+			if let RoomEvent::HoleCards {
+				game_id,
+				guest_id: id,
+				cards,
+			} = self.next_event().await?
+				&& id == guest_id
+				&& game_id > last_id
+			{
+				self.hand = cards;
+				break;
+			}
 		}
+		self.sync().await?;
 
 		Ok(())
 	}
@@ -142,34 +177,20 @@ impl Client {
 					self.sync_game().await?;
 					self.print_game_status();
 				}
-				["fold"] => {
-					let result = self.fold().await;
-					if let Err(err) = result {
-						sprintln!("failed to fold: {err}");
-					}
-				}
-				["check"] => {
-					let result = self.check().await;
-					if let Err(err) = result {
-						sprintln!("failed to check: {err}");
-					}
-				}
-				["call"] => {
-					let result = self.call().await;
-					if let Err(err) = result {
-						sprintln!("failed to call: {err}");
-					}
-				}
-				["raise", chips] => {
-					let result = self.raise(chips.parse()?).await;
-					if let Err(err) = result {
-						sprintln!("failed to raise: {err}");
-					}
-				}
-				["allin"] => {
-					let result = self.allin().await;
+				["fold" | "check" | "call" | "allin" | "bet" | "raise", ..] => {
+					let (action, amount) = match command[..] {
+						["allin"] => ("all_in", None),
+						["bet", chips] => ("bet", Some(chips.parse()?)),
+						["raise", chips] => ("raise_to", Some(chips.parse()?)),
+						[action] => (action, None),
+						_ => {
+							sprintln!("unknown command or wrong usage");
+							continue;
+						}
+					};
+					let result = self.act(action, amount).await;
 					if let Err(err) = result {
-						sprintln!("failed to allin: {err}");
+						sprintln!("failed to {action}: {err}");
 					}
 				}
 				["exit"] => exit(0),
@@ -220,9 +241,10 @@ impl Client {
 				if seat.guest.id == result.guest_id {
 					let hand = if seat.fold {
 						"fold".to_string()
+					} else if let Some(cards) = self.get_hand(game.id, seat.guest.id).await? {
+						Self::pretty_cards(&cards)
 					} else {
-						// TODO: find cards in showdown
-						"not fold".to_string()
+						"muck".to_string()
 					};
 					println!("{i}: {} ({hand}) {:+}", seat.guest.name, result.diff);
 					break;
@@ -233,152 +255,60 @@ impl Client {
 		Ok(())
 	}
 
-	pub async fn fold(&mut self) -> anyhow::Result<()> {
-		let game_id = self.game.as_ref().unwrap().id;
-		let token = self.token.as_ref().unwrap();
-
-		let mut response = self
+	/// Get hole cards of the guest in the game
+	///
+	/// # Return
+	///
+	/// None if the hand is not visible to self
+	pub async fn get_hand(
+		&self,
+		game_id: usize,
+		guest_id: usize,
+	) -> anyhow::Result<Option<Vec<Card>>> {
+		let mut resp = self
 			.awc
-			.post(format!("{}/games/{game_id}/fold", self.server_addr))
-			.bearer_auth(token)
+			.get(format!(
+				"{}/games/{game_id}/hands/{guest_id}",
+				self.server_addr
+			))
+			.bearer_auth(self.token.as_ref().unwrap())
 			.send()
 			.await
 			.map_err(anyhow_error)?;
 
-		if response.status().is_success() {
-			let resp: RoomResponse = response.json().await?;
-			sprintln!("folded in the room: {}", resp.room.id);
-			self.room = Some(resp.room);
-			self.game = resp.game;
-		} else {
-			let resp: ErrorResponse = response.json().await?;
-			sprintln!("failed to fold in the room: {}", resp);
-		}
-
-		Ok(())
-	}
-
-	pub async fn check(&mut self) -> anyhow::Result<()> {
-		let game_id = self.game.as_ref().unwrap().id;
-		let token = self.token.as_ref().unwrap();
-
-		let mut response = self
-			.awc
-			.post(format!("{}/games/{game_id}/bets", self.server_addr))
-			.bearer_auth(token)
-			.send_form(&json!({"chips": 0}))
-			.await
-			.map_err(anyhow_error)?;
-
-		if response.status().is_success() {
-			let resp: RoomResponse = response.json().await?;
-			sprintln!("checked in the room: {}", resp.room.id);
-			self.room = Some(resp.room);
-			self.game = resp.game;
-		} else {
-			let resp: ErrorResponse = response.json().await?;
-			sprintln!("failed to check in the room: {}", resp);
-		}
-
-		Ok(())
-	}
-
-	pub async fn call(&mut self) -> anyhow::Result<()> {
-		let game_id = self.game.as_ref().unwrap().id;
-		let token = self.token.as_ref().unwrap();
-		let guest_id = self.guest.as_ref().unwrap().id;
-		let room = self.room.as_ref().unwrap();
-		let mut chips = 0;
-		for seat in room.seats.iter().filter_map(|s| s.as_ref()) {
-			if seat.guest.id == guest_id {
-				chips = room.max_bet() - seat.bet;
-				break;
-			}
-		}
-
-		let mut response = self
-			.awc
-			.post(format!("{}/games/{game_id}/bets", self.server_addr))
-			.bearer_auth(token)
-			.send_form(&json!({"chips": chips}))
-			.await
-			.map_err(anyhow_error)?;
-
-		if response.status().is_success() {
-			let resp: RoomResponse = response.json().await?;
-			sprintln!("called in the room: {}", resp.room.id);
-			self.room = Some(resp.room);
-			self.game = resp.game;
-		} else {
-			let resp: ErrorResponse = response.json().await?;
-			sprintln!("failed to call in the room: {}", resp);
-		}
-
-		Ok(())
-	}
-
-	pub async fn raise(&mut self, mut chips: usize) -> anyhow::Result<()> {
-		let game_id = self.game.as_ref().unwrap().id;
-		let token = self.token.as_ref().unwrap();
-		let guest_id = self.guest.as_ref().unwrap().id;
-		let room = self.room.as_ref().unwrap();
-		for seat in room.seats.iter().filter_map(|s| s.as_ref()) {
-			if seat.guest.id == guest_id {
-				chips += room.max_bet() - seat.bet;
-				break;
-			}
-		}
-
-		let mut response = self
-			.awc
-			.post(format!("{}/games/{game_id}/bets", self.server_addr))
-			.bearer_auth(token)
-			.send_form(&json!({"chips": chips}))
-			.await
-			.map_err(anyhow_error)?;
-
-		if response.status().is_success() {
-			let resp: RoomResponse = response.json().await?;
-			sprintln!("raised in the room: {}", resp.room.id);
-			self.room = Some(resp.room);
-			self.game = resp.game;
+		if resp.status().is_success() {
+			let resp: HandResponse = resp.json().await?;
+			Ok(Some(resp.hand))
 		} else {
-			let resp: ErrorResponse = response.json().await?;
-			sprintln!("failed to raise in the room: {}", resp);
+			Ok(None)
 		}
-
-		Ok(())
 	}
 
-	pub async fn allin(&mut self) -> anyhow::Result<()> {
+	/// Play a typed action, the server computes the chips
+	pub async fn act(&mut self, action: &str, amount: Option<usize>) -> anyhow::Result<()> {
 		let game_id = self.game.as_ref().unwrap().id;
 		let token = self.token.as_ref().unwrap();
-		let guest_id = self.guest.as_ref().unwrap().id;
-		let room = self.room.as_ref().unwrap();
-		let mut chips = 0;
-		for seat in room.seats.iter().filter_map(|s| s.as_ref()) {
-			if seat.guest.id == guest_id {
-				chips = seat.stack;
-				break;
-			}
+		let mut form = vec![("action", action.to_string())];
+		if let Some(amount) = amount {
+			form.push(("amount", amount.to_string()));
 		}
 
 		let mut response = self
 			.awc
-			.post(format!("{}/games/{game_id}/bets", self.server_addr))
+			.patch(format!("{}/games/{game_id}/records", self.server_addr))
 			.bearer_auth(token)
-			.send_form(&json!({"chips": chips}))
+			.send_form(&form)
 			.await
 			.map_err(anyhow_error)?;
 
 		if response.status().is_success() {
 			let resp: RoomResponse = response.json().await?;
-			sprintln!("allined in the room: {}", resp.room.id);
+			sprintln!("{action} in the room: {}", resp.room.id);
 			self.room = Some(resp.room);
 			self.game = resp.game;
 		} else {
 			let resp: ErrorResponse = response.json().await?;
-			sprintln!("failed to allin in the room: {}", resp);
+			sprintln!("failed to {action} in the room: {}", resp);
 		}
 
 		Ok(())
@@ -432,6 +362,128 @@ impl Client {
 		Ok(())
 	}
 
+	/// Step through a finished game forward and backward
+	pub async fn replay(&self, game_id: usize) -> anyhow::Result<()> {
+		let mut req = self
+			.awc
+			.get(format!("{}/games/{game_id}/replay", self.server_addr));
+		if let Some(token) = &self.token {
+			req = req.bearer_auth(token);
+		}
+		let mut resp = req.send().await.map_err(anyhow_error)?;
+		if !resp.status().is_success() {
+			let resp: ErrorResponse = resp.json().await?;
+			sprintln!("failed to get replay: {resp}");
+			return Ok(());
+		}
+		let replay = resp.json::<ReplayResponse>().await?.replay;
+		if replay.snapshots.is_empty() {
+			sprintln!("nothing to replay");
+			return Ok(());
+		}
+
+		let last = replay.snapshots.len() - 1;
+		let mut step = 0;
+		loop {
+			self.print_snapshot(&replay, step);
+
+			let command = Client::read_command()?;
+			let command: Vec<_> = command.iter().map(std::string::String::as_str).collect();
+			match command[..] {
+				[] | ["next" | "n"] => step = (step + 1).min(last),
+				["prev" | "p"] => step = step.saturating_sub(1),
+				["first"] => step = 0,
+				["last"] => step = last,
+				["help"] => print_replay_help(),
+				["quit" | "q"] => break,
+				_ => sprintln!("unknown command or wrong usage"),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Print the table of the replay after the `step`th record
+	fn print_snapshot(&self, replay: &Replay, step: usize) {
+		let Snapshot {
+			record,
+			round,
+			pot,
+			board,
+			seats,
+		} = &replay.snapshots[step];
+		let name = |guest_id| {
+			replay
+				.players
+				.iter()
+				.find(|p| p.guest.id == guest_id)
+				.map_or("?", |p| p.guest.name.as_str())
+		};
+
+		for seat in seats {
+			let i = seat.position;
+			let status = if seat.fold {
+				"fold".to_string()
+			} else if seat.stack == 0 {
+				format!("allin {}", seat.bet)
+			} else {
+				format!("bet {}", seat.round_bet)
+			};
+			let mut mark = String::new();
+			if !seat.cards.is_empty() {
+				mark.push_str(&format!("({}) ", Self::pretty_cards(&seat.cards)));
+			}
+			if self.guest.as_ref().is_some_and(|g| g.id == seat.guest_id) {
+				mark.push_str("(you)");
+			} else if record.position == Some(i) {
+				mark.push_str("...");
+			}
+			let mut position = String::new();
+			if replay.button == i {
+				position.push_str("[D]");
+			}
+			if replay.sb == Some(i) {
+				position.push_str("[SB]");
+			} else if replay.bb == Some(i) {
+				position.push_str("[BB]");
+			}
+			println!(
+				"{i}: {position} {} {status} ({}) {mark}",
+				name(seat.guest_id),
+				seat.stack
+			);
+		}
+
+		println!("----------------------------------------");
+		println!(
+			"round: {round}, pot: {pot}, common: ({})",
+			Self::pretty_cards(board)
+		);
+		let actor = record.guest_id.map_or("dealer", name);
+		if record.cards.is_empty() {
+			println!(
+				"[{}/{}] {actor}: {} {}",
+				step + 1,
+				replay.snapshots.len(),
+				record.action,
+				record.chips
+			);
+		} else {
+			println!(
+				"[{}/{}] {actor}: {} {}",
+				step + 1,
+				replay.snapshots.len(),
+				record.action,
+				Self::pretty_cards(&record.cards)
+			);
+		}
+		if step + 1 == replay.snapshots.len() {
+			for result in &replay.results {
+				println!("{}: {:+}", name(result.guest_id), result.diff);
+			}
+		}
+	}
+
 	fn print_game_status(&self) {
 		let guest = self.guest.as_ref().unwrap();
 		let room = self.room.as_ref().unwrap();
@@ -447,7 +499,7 @@ impl Client {
 			} else if seat.stack == 0 {
 				format!("allin {}", seat.bet)
 			} else {
-				format!("bet {}", seat.bet)
+				format!("bet {}", seat.round_bet)
 			};
 			let mark = if seat.guest.id == guest.id {
 				format!("({}) (you)", self.pretty_hand())
@@ -456,8 +508,17 @@ impl Client {
 			} else {
 				String::new()
 			};
+			let mut position = String::new();
+			if room.button == i {
+				position.push_str("[D]");
+			}
+			if room.sb == i {
+				position.push_str("[SB]");
+			} else if room.bb == i {
+				position.push_str("[BB]");
+			}
 			println!(
-				"{i}: {} {status} ({}) ({}) {mark}",
+				"{i}: {position} {} {status} ({}) ({}) {mark}",
 				seat.guest.name, seat.stack, seat.guest.bankroll
 			);
 		}
@@ -469,6 +530,12 @@ impl Client {
 			game.pot,
 			self.pretty_common()
 		);
+		if let Some(actions) = game
+			.legal_actions(room)
+			.filter(|actions| room.get_guest(actions.position).unwrap().id == guest.id)
+		{
+			println!("actions: {actions}");
+		}
 	}
 }
 
@@ -480,8 +547,21 @@ fn print_help() {
 		fold
 		check
 		call
-		raise
+		bet <chips>
+		raise <total bet>
 		allin
 		exit"
 	);
 }
+
+fn print_replay_help() {
+	println!(
+		"Command list:
+		help
+		next (or enter)
+		prev
+		first
+		last
+		quit"
+	);
+}
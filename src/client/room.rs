@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 use serde::Deserialize;
 
-use crate::{Room, client::ErrorResponse, sprintln};
+use crate::{Room, RoomSettings, client::ErrorResponse, sprintln};
 
 use super::{Client, error::anyhow_error};
 
@@ -11,7 +11,7 @@ struct RoomResponse {
 }
 
 impl Client {
-	pub async fn new_room(&mut self) -> anyhow::Result<()> {
+	pub async fn new_room(&mut self, settings: &RoomSettings) -> anyhow::Result<()> {
 		if self.token.is_none() {
 			return Err(anyhow!("you should login first"));
 		}
@@ -21,7 +21,7 @@ impl Client {
 			.awc
 			.post(format!("{}/rooms", self.server_addr))
 			.bearer_auth(token)
-			.send()
+			.send_form(settings)
 			.await
 			.map_err(anyhow_error)?;
 
@@ -29,6 +29,7 @@ impl Client {
 			let resp: RoomResponse = response.json().await?;
 			sprintln!("created a new room: {}", resp.room.id);
 			self.room = Some(resp.room);
+			self.connect_events().await?;
 		} else {
 			let resp: ErrorResponse = response.json().await?;
 			sprintln!("failed to create a new room: {}", resp);
@@ -55,6 +56,7 @@ impl Client {
 			let resp: RoomResponse = response.json().await?;
 			sprintln!("joined the room: {}", resp.room.id);
 			self.room = Some(resp.room);
+			self.connect_events().await?;
 		} else {
 			let resp: ErrorResponse = response.json().await?;
 			sprintln!("failed to join the room: {}", resp);
@@ -92,28 +94,4 @@ impl Client {
 
 		Ok(())
 	}
-
-	// /// Wait for game to start
-	// pub async fn wait_game(&self) -> Result<String> {
-	// 	let user = self.user.as_ref().unwrap();
-	// 	let table_id = self.table_id.as_ref().unwrap();
-	// 	loop {
-	// 		let mut response = self
-	// 			.awc
-	// 			.post(format!(
-	// 				"{}/table/game/{}/{}",
-	// 				self.server_addr, user.id, table_id
-	// 			))
-	// 			.send()
-	// 			.await?;
-
-	// 		if response.status().is_success() {
-	// 			let game_id = String::from_utf8(response.body().await?.into()).unwrap();
-	// 			sprintln!("game started: {game_id}");
-	// 			return Ok(game_id);
-	// 		} else {
-	// 			Self::tick().await;
-	// 		}
-	// 	}
-	// }
 }
@@ -1,9 +1,15 @@
+pub mod event;
 pub mod game;
 pub mod guest;
+pub mod history;
+pub mod record;
 pub mod room;
 pub mod utils;
 
+pub use event::*;
 pub use game::*;
 pub use guest::*;
+pub use history::*;
+pub use record::*;
 pub use room::*;
 pub use utils::*;
@@ -0,0 +1,162 @@
+use rusqlite::Transaction;
+
+use crate::{
+	engine::{RoomEvent, SeqEvent},
+	error::Result,
+};
+
+/// Sequence number of the last event of the room, 0 if none
+pub fn last_event_seq(tx: &Transaction, room_id: usize) -> Result<usize> {
+	Ok(tx.query_row(
+		"select coalesce(max(seq), 0) from event where room_id = ?1",
+		(room_id,),
+		|row| row.get(0),
+	)?)
+}
+
+/// Append events to the log of the room
+///
+/// # Return
+///
+/// The events with their sequence numbers, ready to be published
+pub fn insert_events(
+	tx: &Transaction,
+	room_id: usize,
+	events: Vec<RoomEvent>,
+) -> Result<Vec<SeqEvent>> {
+	let mut seq = last_event_seq(tx, room_id)?;
+	let mut seq_events = Vec::new();
+
+	for event in events {
+		seq += 1;
+		tx.execute(
+			"insert into event (room_id, seq, guest_id, data) values (?1, ?2, ?3, ?4)",
+			(
+				room_id,
+				seq,
+				event.owner(),
+				serde_json::to_string(&event).unwrap(),
+			),
+		)?;
+		seq_events.push(SeqEvent { seq, event });
+	}
+
+	Ok(seq_events)
+}
+
+/// Events of the room after the `after`th one which are visible to the guest
+pub fn get_events(
+	tx: &Transaction,
+	room_id: usize,
+	after: usize,
+	guest_id: Option<usize>,
+) -> Result<Vec<SeqEvent>> {
+	let mut stmt = tx.prepare(
+		"select seq, data from event
+			where room_id = ?1 and seq > ?2 and (guest_id is null or guest_id = ?3)
+			order by seq",
+	)?;
+	let rows = stmt.query_map((room_id, after, guest_id), |row| {
+		Ok((row.get::<usize, usize>(0)?, row.get::<usize, String>(1)?))
+	})?;
+
+	let mut events = Vec::new();
+	for row in rows {
+		let (seq, data) = row?;
+		events.push(SeqEvent {
+			seq,
+			event: serde_json::from_str(&data).unwrap(),
+		});
+	}
+
+	Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		Card, RoomSettings,
+		db::{self, new_guest, new_room, open_connection},
+	};
+
+	fn hole_cards(guest_id: usize, cards: &str) -> RoomEvent {
+		RoomEvent::HoleCards {
+			game_id: 1,
+			guest_id,
+			cards: cards.split(' ').map(Card::parse).collect(),
+		}
+	}
+
+	fn seqs(events: &[SeqEvent]) -> Vec<usize> {
+		events.iter().map(|e| e.seq).collect()
+	}
+
+	#[test]
+	fn test_get_events() {
+		db::init();
+		let mut conn = open_connection().unwrap();
+		let tx = conn.transaction().unwrap();
+		let alice = new_guest(&tx, "alice").unwrap();
+		let bob = new_guest(&tx, "bob").unwrap();
+		let room = new_room(&tx, &alice, RoomSettings::default()).unwrap();
+		assert_eq!(last_event_seq(&tx, room.id).unwrap(), 0);
+
+		let events = vec![
+			RoomEvent::SeatJoined {
+				position: 1,
+				guest: bob.clone(),
+			},
+			hole_cards(alice.id, "SA SK"),
+			hole_cards(bob.id, "H2 D7"),
+			RoomEvent::ReadyChanged {
+				guest_id: bob.id,
+				ready: true,
+			},
+		];
+		assert_eq!(
+			seqs(&insert_events(&tx, room.id, events).unwrap()),
+			[1, 2, 3, 4]
+		);
+		assert_eq!(last_event_seq(&tx, room.id).unwrap(), 4);
+
+		// hole cards only go to their owner
+		let get = |after, guest_id| seqs(&get_events(&tx, room.id, after, guest_id).unwrap());
+		assert_eq!(get(0, None), [1, 4]);
+		assert_eq!(get(0, Some(alice.id)), [1, 2, 4]);
+		assert_eq!(get(0, Some(bob.id)), [1, 3, 4]);
+		assert_eq!(get(2, Some(bob.id)), [3, 4]);
+		assert!(get(4, Some(bob.id)).is_empty());
+		assert!(get_events(&tx, room.id + 1, 0, None).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_resume() {
+		db::init();
+		let mut conn = open_connection().unwrap();
+		let tx = conn.transaction().unwrap();
+		let alice = new_guest(&tx, "alice").unwrap();
+		let room = new_room(&tx, &alice, RoomSettings::default()).unwrap();
+
+		let mut received = Vec::new();
+		let mut last = 0;
+		for i in 0..5 {
+			let events = vec![
+				hole_cards(alice.id, "SA SK"),
+				RoomEvent::ReadyChanged {
+					guest_id: alice.id,
+					ready: i % 2 == 0,
+				},
+			];
+			insert_events(&tx, room.id, events).unwrap();
+
+			// resume after the last event ID received
+			let events = get_events(&tx, room.id, last, Some(alice.id)).unwrap();
+			last = events.last().unwrap().seq;
+			received.extend(seqs(&events));
+		}
+
+		assert_eq!(last, last_event_seq(&tx, room.id).unwrap());
+		assert_eq!(received, (1..=10).collect::<Vec<_>>());
+	}
+}
@@ -1,67 +1,223 @@
-use crate::{Card, Game, GameResult, Room, Round, error::Result};
+use crate::{
+	Action, Card, Game, GameResult, PotResult, Room, Round,
+	engine::{Event, GameState},
+	equity::{Equity, PlayerEquity, StreetEquity},
+	error::Result,
+};
 use rusqlite::{OptionalExtension, Transaction};
 
-use super::{bet, max_id};
+use super::{bump_version, get_records, insert_record, max_id, room_by_id};
 
 pub fn max_game_id(tx: &Transaction) -> Result<usize> {
 	max_id(tx, "game")
 }
 
-pub fn new_game(tx: &Transaction, room: &mut Room) -> Result<Game> {
-	// pass SB only before a new game
-	room.pass_sb();
-	tx.execute("update room set sb = ?1 where id = ?2", (room.sb, room.id))?;
-
+/// Start a new game in the room and persist it
+///
+/// # Return
+///
+/// The state and the events of starting the game
+pub fn new_game(tx: &Transaction, room: Room) -> Result<(GameState, Vec<Event>)> {
 	let id = max_game_id(tx)? + 1;
+	let (state, mut events) = GameState::start(room, id, Card::new_deck());
+	let game = &state.game;
+
 	tx.execute(
-		"insert into game (id, room_id, position) values (?1, ?2, ?3)",
-		(id, room.id, room.sb),
+		"insert into game (id, room_id, position, min_raise, button) values (?1, ?2, ?3, ?4, ?5)",
+		(
+			id,
+			game.room_id,
+			game.position,
+			game.min_raise,
+			state.room.button,
+		),
 	)?;
-
-	let mut deck = Card::new_deck();
-
-	for seat in &mut room.seats {
-		match seat {
-			Some(seat) => {
-				seat.bet = 0;
-				seat.fold = false;
-				tx.execute(
-					"update seat set (bet, fold) = (0, false) where room_id = ?1 and guest_id = ?2",
-					(room.id, seat.guest.id),
-				)?;
-				let (c1, c2) = (deck.pop().unwrap(), deck.pop().unwrap());
-				tx.execute(
-					"insert into hand (game_id, guest_id, c1, c2) values (?1, ?2, ?3, ?4)",
-					(id, seat.guest.id, c1, c2),
-				)?;
-			}
-			None => continue,
+	for (position, hand) in state.hands.iter().enumerate() {
+		if let Some(hand) = hand {
+			let seat = state.room.seats[position].as_ref().unwrap();
+			tx.execute(
+				"insert into hand (game_id, guest_id, position, stack, c1, c2)
+					values (?1, ?2, ?3, ?4, ?5, ?6)",
+				(
+					id,
+					seat.guest.id,
+					position,
+					seat.stack + seat.bet,
+					hand[0],
+					hand[1],
+				),
+			)?;
 		}
 	}
-
+	let board = &state.board;
 	tx.execute(
 		"insert into flop (game_id, c1, c2, c3) values (?1, ?2, ?3, ?4)",
-		(
-			id,
-			deck.pop().unwrap(),
-			deck.pop().unwrap(),
-			deck.pop().unwrap(),
-		),
+		(id, board[0], board[1], board[2]),
 	)?;
 	tx.execute(
 		"insert into turn (game_id, card) values (?1, ?2)",
-		(id, deck.pop().unwrap()),
+		(id, board[3]),
 	)?;
 	tx.execute(
 		"insert into river (game_id, card) values (?1, ?2)",
-		(id, deck.pop().unwrap()),
+		(id, board[4]),
+	)?;
+
+	save_state(tx, &state, &mut events)?;
+
+	Ok((state, events))
+}
+
+/// Load the state of the game with its room and cards
+///
+/// # Return
+///
+/// None if game not found
+pub fn load_state(tx: &Transaction, game_id: usize) -> Result<Option<GameState>> {
+	let Some(game) = game_by_id(tx, game_id)? else {
+		return Ok(None);
+	};
+	let room = room_by_id(tx, game.room_id)?.unwrap();
+
+	let shown_guests = get_shown_guests(tx, game_id)?;
+	let mut hands = [const { None }; Room::MAX_SEATS];
+	let mut shown = [false; Room::MAX_SEATS];
+	for (position, seat) in room.seats.iter().enumerate() {
+		if let Some(seat) = seat {
+			hands[position] = get_hand(tx, game_id, seat.guest.id)?;
+			shown[position] = shown_guests.contains(&seat.guest.id);
+		}
+	}
+	let mut board = get_flop(tx, game_id)?.unwrap();
+	board.push(get_turn(tx, game_id)?.unwrap());
+	board.push(get_river(tx, game_id)?.unwrap());
+
+	Ok(Some(GameState {
+		room,
+		game,
+		hands,
+		board,
+		shown,
+	}))
+}
+
+/// Persist the state and the events returned by the engine
+///
+/// Sequence numbers of the records in `events` are set by this function
+pub fn save_state(tx: &Transaction, state: &GameState, events: &mut [Event]) -> Result<()> {
+	let (room, game) = (&state.room, &state.game);
+
+	tx.execute(
+		"update room set (button, sb, bb) = (?1, ?2, ?3) where id = ?4",
+		(room.button, room.sb, room.bb, room.id),
 	)?;
+	for seat in room.seats.iter().flatten() {
+		tx.execute(
+			"update seat set (ready, stack, bet, round_bet, fold) = (?1, ?2, ?3, ?4, ?5)
+				where room_id = ?6 and guest_id = ?7",
+			(
+				seat.ready,
+				seat.stack,
+				seat.bet,
+				seat.round_bet,
+				seat.fold,
+				room.id,
+				seat.guest.id,
+			),
+		)?;
+	}
+	tx.execute(
+		"update game set (round, pot, position, min_raise, acted, aggressor)
+			= (?1, ?2, ?3, ?4, ?5, ?6) where id = ?7",
+		(
+			game.round,
+			game.pot,
+			game.position,
+			game.min_raise,
+			game.acted,
+			game.aggressor,
+			game.id,
+		),
+	)?;
+
+	for event in events {
+		match event {
+			Event::Record(record) => insert_record(tx, record)?,
+			Event::Round(_) => (),
+			Event::Equity(equity) => insert_equity(tx, equity)?,
+			Event::Over { results, pots } => {
+				insert_results(tx, game.id, results, pots)?;
+				for seat in room.seats.iter().flatten() {
+					tx.execute(
+						"update guest set bankroll = ?1 where id = ?2",
+						(seat.guest.bankroll, seat.guest.id),
+					)?;
+				}
+			}
+		}
+	}
+
+	bump_version(tx, room.id)?;
+
+	Ok(())
+}
+
+/// Insert the results and the pots of a finished game
+pub fn insert_results(
+	tx: &Transaction,
+	game_id: usize,
+	results: &[GameResult],
+	pots: &[PotResult],
+) -> Result<()> {
+	for pot in pots {
+		for &guest_id in &pot.eligible {
+			let won = pot
+				.winners
+				.iter()
+				.find(|&&(id, _)| id == guest_id)
+				.map_or(0, |&(_, won)| won);
+			tx.execute(
+				"insert into pot (game_id, number, guest_id, won) values (?1, ?2, ?3, ?4)",
+				(game_id, pot.number, guest_id, won),
+			)?;
+		}
+	}
+	for result in results {
+		tx.execute(
+			"insert into result (game_id, guest_id, diff, winner, uncalled)
+				values (?1, ?2, ?3, ?4, ?5)",
+			(
+				game_id,
+				result.guest_id,
+				result.diff,
+				result.winner,
+				result.uncalled,
+			),
+		)?;
+	}
+
+	Ok(())
+}
 
-	let mut game = Game::new(id, room.id, room.sb);
-	bet(tx, room, &mut game, 1)?;
-	bet(tx, room, &mut game, 2)?;
+/// Insert the equities of the players after a street of an all-in runout
+pub fn insert_equity(tx: &Transaction, equity: &StreetEquity) -> Result<()> {
+	for player in &equity.players {
+		tx.execute(
+			"insert into equity (game_id, round, guest_id, position, win, tie, equity)
+				values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+			(
+				equity.game_id,
+				equity.round,
+				player.guest_id,
+				player.position,
+				player.equity.win,
+				player.equity.tie,
+				player.equity.equity,
+			),
+		)?;
+	}
 
-	Ok(game)
+	Ok(())
 }
 
 /// Get game by ID
@@ -72,7 +228,7 @@ pub fn new_game(tx: &Transaction, room: &mut Room) -> Result<Game> {
 pub fn game_by_id(tx: &Transaction, id: usize) -> Result<Option<Game>> {
 	Ok(tx
 		.query_row(
-			"select room_id, round, pot, position, raise_position from game where id = ?1",
+			"select room_id, round, pot, position, min_raise, acted, aggressor from game where id = ?1",
 			(id,),
 			|row| {
 				Ok(Game {
@@ -81,23 +237,57 @@ pub fn game_by_id(tx: &Transaction, id: usize) -> Result<Option<Game>> {
 					round: row.get(1)?,
 					pot: row.get(2)?,
 					position: row.get(3)?,
-					raise_position: row.get(4)?,
+					min_raise: row.get(4)?,
+					acted: row.get(5)?,
+					aggressor: row.get(6)?,
 				})
 			},
 		)
 		.optional()?)
 }
 
+/// The button and the UTC time when the game started
+pub fn game_start(tx: &Transaction, game_id: usize) -> Result<(usize, String)> {
+	Ok(tx.query_row(
+		"select button, created_at from game where id = ?1",
+		(game_id,),
+		|row| Ok((row.get(0)?, row.get(1)?)),
+	)?)
+}
+
+/// IDs of the guests dealt in the game in the order of their positions
+pub fn get_players(tx: &Transaction, game_id: usize) -> Result<Vec<usize>> {
+	let mut stmt = tx.prepare("select guest_id from hand where game_id = ?1 order by position")?;
+	let mut players = Vec::new();
+	for guest_id in stmt.query_map((game_id,), |row| row.get(0))? {
+		players.push(guest_id?);
+	}
+
+	Ok(players)
+}
+
 pub fn get_hand(tx: &Transaction, game_id: usize, guest_id: usize) -> Result<Option<Vec<Card>>> {
 	Ok(tx
 		.query_row(
-			"select c1, c2 from hand where game_id = ?1 and guest_id = ?2",
+			"select c1, c2 from hand where game_id = ?1 and guest_id = ?2 and c1 is not null",
 			(game_id, guest_id),
 			|row| Ok(vec![row.get(0)?, row.get(1)?]),
 		)
 		.optional()?)
 }
 
+/// IDs of the guests who have shown their hands in the game
+pub fn get_shown_guests(tx: &Transaction, game_id: usize) -> Result<Vec<usize>> {
+	let mut stmt =
+		tx.prepare("select guest_id from record where game_id = ?1 and action = ?2 order by seq")?;
+	let mut guests = Vec::new();
+	for guest_id in stmt.query_map((game_id, Action::Show), |row| row.get(0))? {
+		guests.push(guest_id?);
+	}
+
+	Ok(guests)
+}
+
 pub fn get_flop(tx: &Transaction, game_id: usize) -> Result<Option<Vec<Card>>> {
 	Ok(tx
 		.query_row(
@@ -128,7 +318,14 @@ pub fn get_river(tx: &Transaction, game_id: usize) -> Result<Option<Card>> {
 		.optional()?)
 }
 
+/// Common cards seen in the game
+///
+/// Once the game is over, only the streets actually dealt are returned.
 pub fn get_common(tx: &Transaction, game: &Game) -> Result<Vec<Card>> {
+	if game.is_over() {
+		return get_board(tx, game.id);
+	}
+
 	let mut cards = Vec::new();
 
 	if game.round >= Round::Flop {
@@ -144,27 +341,104 @@ pub fn get_common(tx: &Transaction, game: &Game) -> Result<Vec<Card>> {
 	Ok(cards)
 }
 
-pub fn update_round(tx: &Transaction, room: &Room, game: &mut Game) -> Result<bool> {
-	let result = game.update(room);
-
-	if result {
-		tx.execute(
-			"update game set round = ?1 where id = ?2",
-			(game.round, game.id),
-		)?;
-	}
-
-	Ok(result)
+/// Common cards dealt by the records of the game
+pub fn get_board(tx: &Transaction, game_id: usize) -> Result<Vec<Card>> {
+	Ok(get_records(tx, game_id, 0)?
+		.into_iter()
+		.filter(|r| r.action == Action::Deal && r.guest_id.is_none())
+		.flat_map(|r| r.cards)
+		.collect())
 }
 
 pub fn get_results(tx: &Transaction, game_id: usize) -> Result<Vec<GameResult>> {
-	let mut stmt = tx.prepare("select guest_id, diff from result where game_id = ?1")?;
+	let mut stmt =
+		tx.prepare("select guest_id, diff, winner, uncalled from result where game_id = ?1")?;
 	let mut results = Vec::new();
 	for result in stmt.query_map((game_id,), |row| {
-		Ok(GameResult::new(game_id, row.get(0)?, row.get(1)?))
+		Ok(GameResult::new(
+			game_id,
+			row.get(0)?,
+			row.get(1)?,
+			row.get(2)?,
+			row.get(3)?,
+		))
 	})? {
 		results.push(result?);
 	}
 
 	Ok(results)
 }
+
+/// Get the main pot and side pots of a finished game
+pub fn get_pots(tx: &Transaction, game_id: usize) -> Result<Vec<PotResult>> {
+	let mut stmt = tx.prepare(
+		"select number, guest_id, won from pot where game_id = ?1 order by number, guest_id",
+	)?;
+	let rows = stmt.query_map((game_id,), |row| {
+		Ok((
+			row.get::<usize, usize>(0)?,
+			row.get::<usize, usize>(1)?,
+			row.get::<usize, usize>(2)?,
+		))
+	})?;
+
+	let mut pots: Vec<PotResult> = Vec::new();
+	for row in rows {
+		let (number, guest_id, won) = row?;
+		if pots.last().is_none_or(|pot| pot.number != number) {
+			pots.push(PotResult {
+				game_id,
+				number,
+				chips: 0,
+				eligible: Vec::new(),
+				winners: Vec::new(),
+			});
+		}
+		let pot = pots.last_mut().unwrap();
+		pot.chips += won;
+		pot.eligible.push(guest_id);
+		if won > 0 {
+			pot.winners.push((guest_id, won));
+		}
+	}
+
+	Ok(pots)
+}
+
+/// Equities of the players after each street of an all-in runout, empty if
+/// the game had none
+pub fn get_equities(tx: &Transaction, game_id: usize) -> Result<Vec<StreetEquity>> {
+	let mut stmt = tx.prepare(
+		"select round, guest_id, position, win, tie, equity from equity
+			where game_id = ?1 order by rowid",
+	)?;
+	let rows = stmt.query_map((game_id,), |row| {
+		Ok((
+			row.get::<usize, Round>(0)?,
+			PlayerEquity {
+				guest_id: row.get(1)?,
+				position: row.get(2)?,
+				equity: Equity {
+					win: row.get(3)?,
+					tie: row.get(4)?,
+					equity: row.get(5)?,
+				},
+			},
+		))
+	})?;
+
+	let mut equities: Vec<StreetEquity> = Vec::new();
+	for row in rows {
+		let (round, player) = row?;
+		if equities.last().is_none_or(|e| e.round != round) {
+			equities.push(StreetEquity {
+				game_id,
+				round,
+				players: Vec::new(),
+			});
+		}
+		equities.last_mut().unwrap().players.push(player);
+	}
+
+	Ok(equities)
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use rusqlite::Transaction;
+
+use crate::{
+	Action, Game, Guest, Round,
+	error::Result,
+	history::{HandHistory, HistoryPlayer},
+};
+
+use super::{
+	game_by_id, game_start, get_pots, get_records, get_results, guest_by_id, insert_record,
+	insert_results, is_imported, max_game_id, max_guest_id, max_room_id, room_by_id,
+};
+
+/// Load the history of the game for the `hero`
+///
+/// Hole cards of the other players are left out, their hands can only be
+/// seen when they are shown.
+///
+/// # Return
+///
+/// None if game not found
+pub fn load_history(
+	tx: &Transaction,
+	game_id: usize,
+	hero: Option<usize>,
+) -> Result<Option<HandHistory>> {
+	let Some(game) = game_by_id(tx, game_id)? else {
+		return Ok(None);
+	};
+	let room = room_by_id(tx, game.room_id)?.unwrap();
+	let (button, started_at) = game_start(tx, game_id)?;
+
+	let players = get_dealt_players(tx, game_id)?;
+
+	// hole cards in imported games were already public in the imported history
+	let imported = is_imported(tx, room.id)?;
+	let records = get_records(tx, game_id, 0)?
+		.into_iter()
+		.filter(|r| imported || !r.is_hole_cards() || r.guest_id == hero)
+		.collect();
+
+	Ok(Some(HandHistory {
+		game,
+		settings: room.settings,
+		started_at,
+		button,
+		players,
+		records,
+		results: get_results(tx, game_id)?,
+		pots: get_pots(tx, game_id)?,
+		hero,
+	}))
+}
+
+/// Players dealt in the game with their stacks before antes and blinds
+pub fn get_dealt_players(tx: &Transaction, game_id: usize) -> Result<Vec<HistoryPlayer>> {
+	let mut stmt = tx.prepare(
+		"select guest_id, position, stack from hand where game_id = ?1 order by position",
+	)?;
+	let rows = stmt.query_map((game_id,), |row| {
+		Ok((
+			row.get::<usize, usize>(0)?,
+			row.get::<usize, usize>(1)?,
+			row.get::<usize, usize>(2)?,
+		))
+	})?;
+	let mut players = Vec::new();
+	for row in rows {
+		let (guest_id, position, stack) = row?;
+		players.push(HistoryPlayer {
+			position,
+			guest: guest_by_id(tx, guest_id)?.unwrap(),
+			stack,
+		});
+	}
+
+	Ok(players)
+}
+
+/// Import the history of a finished game into a new read-only room
+///
+/// The players are imported as new guests without tokens, and bankrolls are
+/// left untouched.
+///
+/// # Return
+///
+/// The imported game
+pub fn import_history(tx: &Transaction, history: HandHistory) -> Result<Game> {
+	let settings = &history.settings;
+	settings.validate()?;
+
+	let room_id = max_room_id(tx)? + 1;
+	tx.execute(
+		"insert into room(id, button, small_blind, big_blind, ante, big_blind_ante,
+			starting_stack, min_stack, imported) values(?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, true)",
+		(
+			room_id,
+			history.button,
+			settings.small_blind,
+			settings.big_blind,
+			settings.ante,
+			settings.big_blind_ante,
+			settings.starting_stack,
+			settings.min_stack,
+		),
+	)?;
+
+	let mut guests = HashMap::new();
+	for player in &history.players {
+		let guest = import_guest(tx, &player.guest.name, player.position)?;
+		let diff = history
+			.results
+			.iter()
+			.find(|r| r.guest_id == player.guest.id)
+			.map_or(0, |r| r.diff);
+		tx.execute(
+			"insert into seat(room_id, position, guest_id, stack) values(?1, ?2, ?3, ?4)",
+			(
+				room_id,
+				player.position,
+				guest.id,
+				player.stack.saturating_add_signed(diff),
+			),
+		)?;
+		guests.insert(player.guest.id, guest.id);
+	}
+
+	let game_id = max_game_id(tx)? + 1;
+	tx.execute(
+		"insert into game (id, room_id, round, pot, button, created_at)
+			values (?1, ?2, ?3, ?4, ?5, ?6)",
+		(
+			game_id,
+			room_id,
+			Round::Over,
+			history.game.pot,
+			history.button,
+			&history.started_at,
+		),
+	)?;
+
+	for player in &history.players {
+		// the last known hole cards of the player
+		let cards = history
+			.records
+			.iter()
+			.filter(|r| r.guest_id == Some(player.guest.id) && r.cards.len() == 2)
+			.filter(|r| matches!(r.action, Action::Deal | Action::Show))
+			.map(|r| &r.cards)
+			.next_back();
+		tx.execute(
+			"insert into hand (game_id, guest_id, position, stack, c1, c2)
+				values (?1, ?2, ?3, ?4, ?5, ?6)",
+			(
+				game_id,
+				guests[&player.guest.id],
+				player.position,
+				player.stack,
+				cards.map(|cards| cards[0]),
+				cards.map(|cards| cards[1]),
+			),
+		)?;
+	}
+
+	let board = history.board();
+	if board.len() >= 3 {
+		tx.execute(
+			"insert into flop (game_id, c1, c2, c3) values (?1, ?2, ?3, ?4)",
+			(game_id, board[0], board[1], board[2]),
+		)?;
+	}
+	if let Some(card) = board.get(3) {
+		tx.execute(
+			"insert into turn (game_id, card) values (?1, ?2)",
+			(game_id, card),
+		)?;
+	}
+	if let Some(card) = board.get(4) {
+		tx.execute(
+			"insert into river (game_id, card) values (?1, ?2)",
+			(game_id, card),
+		)?;
+	}
+
+	for mut record in history.records {
+		record.game_id = game_id;
+		record.guest_id = record.guest_id.map(|id| guests[&id]);
+		insert_record(tx, &mut record)?;
+	}
+	let results: Vec<_> = history
+		.results
+		.into_iter()
+		.map(|mut result| {
+			result.game_id = game_id;
+			result.guest_id = guests[&result.guest_id];
+			result
+		})
+		.collect();
+	let pots: Vec<_> = history
+		.pots
+		.into_iter()
+		.map(|mut pot| {
+			pot.game_id = game_id;
+			pot.eligible = pot.eligible.iter().map(|id| guests[id]).collect();
+			pot.winners = pot
+				.winners
+				.iter()
+				.map(|&(id, won)| (guests[&id], won))
+				.collect();
+			pot
+		})
+		.collect();
+	insert_results(tx, game_id, &results, &pots)?;
+
+	Ok(game_by_id(tx, game_id)?.unwrap())
+}
+
+/// Insert a guest without token for an imported player
+///
+/// Names which are not allowed for guests are replaced by the seat.
+fn import_guest(tx: &Transaction, name: &str, position: usize) -> Result<Guest> {
+	let name: String = name.chars().take(32).collect();
+	let name = if name.len() < 3
+		|| name.len() > 32
+		|| ["client", "server", "system"].contains(&name.as_str())
+	{
+		format!("seat {}", position + 1)
+	} else {
+		name
+	};
+
+	let id = max_guest_id(tx)? + 1;
+	tx.execute("insert into guest(id, name) values(?1, ?2)", (id, &name))?;
+
+	Ok(Guest::new(id, &name))
+}
@@ -0,0 +1,63 @@
+use rusqlite::Transaction;
+
+use crate::{Card, Record, error::Result};
+
+/// Insert the record as the last one of its game
+///
+/// The sequence number of `record` is set by this function
+pub fn insert_record(tx: &Transaction, record: &mut Record) -> Result<()> {
+	record.seq = tx.query_row(
+		"select count(*) from record where game_id = ?1",
+		(record.game_id,),
+		|row| row.get(0),
+	)?;
+	let cards: String = record.cards.iter().map(ToString::to_string).collect();
+
+	tx.execute(
+		"insert into record (game_id, seq, round, position, guest_id, action, chips, cards)
+			values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+		(
+			record.game_id,
+			record.seq,
+			record.round,
+			record.position,
+			record.guest_id,
+			record.action,
+			record.chips,
+			cards,
+		),
+	)?;
+
+	Ok(())
+}
+
+/// Get records of the game in order, starting from the `offset`th one
+pub fn get_records(tx: &Transaction, game_id: usize, offset: usize) -> Result<Vec<Record>> {
+	let mut stmt = tx.prepare(
+		"select seq, round, position, guest_id, action, chips, cards from record
+			where game_id = ?1 and seq >= ?2 order by seq",
+	)?;
+	let rows = stmt.query_map((game_id, offset), |row| {
+		let cards: String = row.get(6)?;
+		Ok(Record {
+			game_id,
+			seq: row.get(0)?,
+			round: row.get(1)?,
+			position: row.get(2)?,
+			guest_id: row.get(3)?,
+			action: row.get(4)?,
+			chips: row.get(5)?,
+			cards: (0..cards.len())
+				.step_by(2)
+				.map(|i| Card::parse(&cards[i..i + 2]))
+				.collect(),
+		})
+	})?;
+
+	let mut records = Vec::new();
+	for record in rows {
+		records.push(record?);
+	}
+
+	Ok(records)
+}
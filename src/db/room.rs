@@ -1,10 +1,8 @@
-use std::cmp::Ordering;
-
 use rusqlite::{OptionalExtension, Transaction};
 
-use crate::db::{game_by_id, get_common, get_hand};
-use crate::error::{Result, conflict_error};
-use crate::{Game, Guest, Hand, Room, Seat};
+use crate::db::game_by_id;
+use crate::error::Result;
+use crate::{Game, Guest, Room, RoomSettings, Seat};
 
 use super::{guest_by_id, max_id};
 
@@ -12,17 +10,68 @@ pub fn max_room_id(tx: &Transaction) -> Result<usize> {
 	max_id(tx, "room")
 }
 
-pub fn new_room(tx: &Transaction, guest: &Guest) -> Result<Room> {
+pub fn new_room(tx: &Transaction, guest: &Guest, settings: RoomSettings) -> Result<Room> {
+	settings.validate()?;
+
 	let id = max_room_id(tx)? + 1;
-	tx.execute("insert into room(id) values(?1)", (id,))?;
 	tx.execute(
-		"insert into seat(room_id, position, guest_id) values(?1, 0, ?2)",
-		(id, guest.id),
+		"insert into room(id, small_blind, big_blind, ante, big_blind_ante, starting_stack, min_stack)
+			values(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+		(
+			id,
+			settings.small_blind,
+			settings.big_blind,
+			settings.ante,
+			settings.big_blind_ante,
+			settings.starting_stack,
+			settings.min_stack,
+		),
 	)?;
-	let room = Room::with_guest(id, guest);
+	tx.execute(
+		"insert into seat(room_id, position, guest_id, stack) values(?1, 0, ?2, ?3)",
+		(id, guest.id, settings.starting_stack),
+	)?;
+	let room = Room::with_guest(id, guest, settings);
 	Ok(room)
 }
 
+/// Version of the room, which is bumped by every change of the room or its games
+///
+/// # Return
+///
+/// None if room not found
+pub fn room_version(tx: &Transaction, room_id: usize) -> Result<Option<usize>> {
+	Ok(tx
+		.query_row(
+			"select version from room where id = ?1",
+			(room_id,),
+			|row| row.get(0),
+		)
+		.optional()?)
+}
+
+/// Bump the version of the room after changing it or its games
+///
+/// # Return
+///
+/// The new version
+pub fn bump_version(tx: &Transaction, room_id: usize) -> Result<usize> {
+	Ok(tx.query_row(
+		"update room set version = version + 1 where id = ?1 returning version",
+		(room_id,),
+		|row| row.get(0),
+	)?)
+}
+
+/// Whether the room holds imported games, which are read-only
+pub fn is_imported(tx: &Transaction, room_id: usize) -> Result<bool> {
+	Ok(tx.query_row(
+		"select imported from room where id = ?1",
+		(room_id,),
+		|row| row.get(0),
+	)?)
+}
+
 /// Whether the guest is ready in the room
 pub fn is_ready(tx: &Transaction, room_id: usize, guest_id: usize) -> Result<bool> {
 	let ready = tx.query_row(
@@ -40,16 +89,33 @@ pub fn is_ready(tx: &Transaction, room_id: usize, guest_id: usize) -> Result<boo
 /// None if room not found
 pub fn room_by_id(tx: &Transaction, id: usize) -> Result<Option<Room>> {
 	let mut room = Room::new(id);
-	let sb: Option<usize> = tx
-		.query_row("select sb from room where id = ?1", (id,), |row| row.get(0))
+	let found = tx
+		.query_row(
+			"select button, sb, bb, small_blind, big_blind, ante, big_blind_ante,
+				starting_stack, min_stack from room where id = ?1",
+			(id,),
+			|row| {
+				room.button = row.get(0)?;
+				room.sb = row.get(1)?;
+				room.bb = row.get(2)?;
+				room.settings = RoomSettings {
+					small_blind: row.get(3)?,
+					big_blind: row.get(4)?,
+					ante: row.get(5)?,
+					big_blind_ante: row.get(6)?,
+					starting_stack: row.get(7)?,
+					min_stack: row.get(8)?,
+				};
+				Ok(())
+			},
+		)
 		.optional()?;
-	match sb {
-		None => return Ok(None),
-		Some(sb) => room.sb = sb,
+	if found.is_none() {
+		return Ok(None);
 	}
 
 	let mut stmt = tx.prepare(
-		"select position, guest_id, ready, stack, bet, fold from seat where room_id = ?1",
+		"select position, guest_id, ready, stack, bet, round_bet, fold from seat where room_id = ?1",
 	)?;
 	let rows = stmt.query_map((id,), |row| {
 		Ok((
@@ -59,16 +125,18 @@ pub fn room_by_id(tx: &Transaction, id: usize) -> Result<Option<Room>> {
 			row.get(3)?,
 			row.get(4)?,
 			row.get(5)?,
+			row.get(6)?,
 		))
 	})?;
 
 	for row in rows {
-		let (position, guest_id, ready, stack, bet, fold) = row?;
+		let (position, guest_id, ready, stack, bet, round_bet, fold) = row?;
 		room.seats[position] = Some(Seat {
 			guest: guest_by_id(tx, guest_id)?.unwrap(),
 			ready,
 			stack,
 			bet,
+			round_bet,
 			fold,
 		});
 	}
@@ -76,21 +144,48 @@ pub fn room_by_id(tx: &Transaction, id: usize) -> Result<Option<Room>> {
 	Ok(Some(room))
 }
 
+/// Filters of the games of a room, None matches every game
+#[derive(Debug, Default)]
+pub struct GameFilter {
+	/// Started at or after this UTC time, like `2025-01-31 18:30:00`
+	pub from: Option<String>,
+	/// Started before this UTC time
+	pub to: Option<String>,
+	/// Dealt to this guest
+	pub guest_id: Option<usize>,
+}
+
 pub fn get_games(
 	tx: &Transaction,
 	id: usize,
 	asc: bool,
 	limit: usize,
 	offset: usize,
+) -> Result<Vec<Game>> {
+	find_games(tx, id, &GameFilter::default(), asc, limit, offset)
+}
+
+/// Get games of the room matching the filter, ordered by ID
+pub fn find_games(
+	tx: &Transaction,
+	id: usize,
+	filter: &GameFilter,
+	asc: bool,
+	limit: usize,
+	offset: usize,
 ) -> Result<Vec<Game>> {
 	let mut stmt = tx.prepare(
 		format!(
-			"select id from game where room_id = ?1 order by id {} limit ?2 offset ?3",
+			"select id from game where room_id = ?1 and {GAME_FILTER}
+				order by id {} limit ?5 offset ?6",
 			if asc { "ASC" } else { "DESC" }
 		)
 		.as_str(),
 	)?;
-	let rows = stmt.query_map((id, limit, offset), |row| row.get::<usize, usize>(0))?;
+	let rows = stmt.query_map(
+		(id, &filter.from, &filter.to, filter.guest_id, limit, offset),
+		|row| row.get::<usize, usize>(0),
+	)?;
 
 	let mut games = Vec::new();
 	for game_id in rows {
@@ -100,129 +195,104 @@ pub fn get_games(
 	Ok(games)
 }
 
+/// Count games of the room matching the filter
+pub fn count_games(tx: &Transaction, id: usize, filter: &GameFilter) -> Result<usize> {
+	Ok(tx.query_row(
+		format!("select count(*) from game where room_id = ?1 and {GAME_FILTER}").as_str(),
+		(id, &filter.from, &filter.to, filter.guest_id),
+		|row| row.get(0),
+	)?)
+}
+
+/// Condition of [`GameFilter`] on the parameters ?2, ?3 and ?4
+const GAME_FILTER: &str = "(?2 is null or created_at >= ?2)
+	and (?3 is null or created_at < ?3)
+	and (?4 is null or exists (select 1 from hand where game_id = game.id and guest_id = ?4))";
+
 /// Get current running game of the room
 pub fn get_running_game(tx: &Transaction, id: usize) -> Result<Option<Game>> {
 	let game = get_games(tx, id, false, 1, 0)?.pop();
 	Ok(game.filter(|game| !game.is_over()))
 }
 
-/// Bet as the current player of the game
-pub fn bet(tx: &Transaction, room: &mut Room, game: &mut Game, chips: usize) -> Result<()> {
-	let max_bet = room.max_bet();
-	let seat = room.seats[game.position].as_mut().unwrap();
-
-	assert!(chips <= seat.stack);
-	seat.stack -= chips;
-	seat.bet += chips;
-	game.pot += chips;
-	if seat.bet > max_bet {
-		game.raise_position = game.position;
-		tx.execute(
-			"update game set raise_position = ?1 where id = ?2",
-			(game.raise_position, game.id),
-		)?;
-	} else if seat.bet != max_bet && !seat.allin() {
-		return Err(conflict_error("should bet more"));
-	}
-	tx.execute(
-		"update seat set (stack, bet) = (?1, ?2) where room_id = ?3 and guest_id = ?4",
-		(seat.stack, seat.bet, room.id, seat.guest.id),
-	)?;
-	tx.execute(
-		"update game set pot = ?1 where id = ?2",
-		(game.pot, game.id),
-	)?;
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::{self, new_game, new_guest, new_room, open_connection};
 
-	game.pass(room);
-	tx.execute(
-		"update game set position = ?1 where id = ?2",
-		(game.position, game.id),
-	)?;
-
-	Ok(())
-}
-
-/// Fold as the current player of the game
-pub fn fold(tx: &Transaction, room: &mut Room, game: &mut Game) -> Result<()> {
-	let seat = room.seats[game.position].as_mut().unwrap();
-	assert!(!seat.fold);
-	seat.fold = true;
-	tx.execute(
-		"update seat set fold = true where room_id = ?1 and guest_id = ?2",
-		(room.id, seat.guest.id),
-	)?;
-
-	game.pass(room);
-	tx.execute(
-		"update game set position = ?1 where id = ?2",
-		(game.position, game.id),
-	)?;
-
-	Ok(())
-}
-
-/// Compute game result
-///
-/// # Note
-///
-/// Only use this function when the game is over
-pub fn calc_result(tx: &Transaction, room: &mut Room, game: &Game) -> Result<()> {
-	let ids: Vec<_> = room
-		.seats
-		.iter()
-		.filter(|s| s.as_ref().is_some_and(|s| !s.fold))
-		.filter_map(|s| s.as_ref().map(|s| s.guest.id))
-		.collect();
-
-	let mut win_i = 0;
-	let common = get_common(tx, game)?;
-	let mut hands = Vec::new();
-	for id in &ids {
-		hands.push(get_hand(tx, game.id, *id)?.unwrap());
-	}
-	for i in 1..hands.len() {
-		let h1 = Hand::calc_best_hand(&common, &hands[win_i]);
-		let h2 = Hand::calc_best_hand(&common, &hands[i]);
-		match h1.cmp(&h2) {
-			Ordering::Less => {
-				win_i = i;
-			}
-			Ordering::Equal => todo!(),
-			Ordering::Greater => (),
-		}
+	fn ids(games: &[Game]) -> Vec<usize> {
+		games.iter().map(|g| g.id).collect()
 	}
 
-	let win_id = ids[win_i];
+	#[test]
+	fn test_find_games() {
+		db::init();
+		let mut conn = open_connection().unwrap();
+		let tx = conn.transaction().unwrap();
+		let guests: Vec<_> = ["alice", "bob", "carol"]
+			.into_iter()
+			.map(|name| new_guest(&tx, name).unwrap())
+			.collect();
+		let room = new_room(&tx, &guests[0], RoomSettings::default()).unwrap();
+		let other = new_room(&tx, &guests[0], RoomSettings::default()).unwrap();
 
-	// TODO: side pot logic
-	for seat in room.seats.iter_mut().filter_map(|s| s.as_mut()) {
-		let diff = if seat.guest.id == win_id {
-			seat.stack += game.pot;
+		// alice and bob, alice and carol, then bob and carol, a day apart
+		for (day, (a, b)) in [(0, 1), (0, 2), (1, 2)].into_iter().enumerate() {
+			let mut players = Room::with_guest(room.id, &guests[a], RoomSettings::default());
+			players.insert(guests[b].clone()).unwrap();
+			let (state, _) = new_game(&tx, players).unwrap();
 			tx.execute(
-				"update seat set stack = ?1 where room_id = ?2 and guest_id = ?3",
-				(seat.stack, room.id, win_id),
-			)?;
+				"update game set created_at = ?1 where id = ?2",
+				(format!("2025-01-0{} 10:00:00", day + 1), state.game.id),
+			)
+			.unwrap();
+		}
+		let mut players = Room::with_guest(other.id, &guests[0], RoomSettings::default());
+		players.insert(guests[1].clone()).unwrap();
+		new_game(&tx, players).unwrap();
 
-			(game.pot - seat.bet) as isize
-		} else {
-			-(seat.bet as isize)
+		let find = |filter: &GameFilter| {
+			let games = ids(&find_games(&tx, room.id, filter, true, 10, 0).unwrap());
+			assert_eq!(count_games(&tx, room.id, filter).unwrap(), games.len());
+			games
 		};
-		seat.guest.bankroll += diff;
-		seat.ready = false;
-
-		tx.execute(
-			"insert into result (game_id, guest_id, diff) values (?1, ?2, ?3)",
-			(game.id, seat.guest.id, diff),
-		)?;
-		tx.execute(
-			"update guest set bankroll = ?1 where id = ?2",
-			(seat.guest.bankroll, seat.guest.id),
-		)?;
-		tx.execute(
-			"update seat set ready = false where room_id = ?1 and guest_id = ?2",
-			(room.id, seat.guest.id),
-		)?;
-	}
+		let at = |time: &str| Some(time.to_string());
+		assert_eq!(find(&GameFilter::default()), [1, 2, 3]);
+		let from = GameFilter {
+			from: at("2025-01-02 10:00:00"),
+			..GameFilter::default()
+		};
+		assert_eq!(find(&from), [2, 3]);
+		// the end is excluded
+		let to = GameFilter {
+			to: at("2025-01-03 10:00:00"),
+			..GameFilter::default()
+		};
+		assert_eq!(find(&to), [1, 2]);
+		let between = GameFilter {
+			from: at("2025-01-02"),
+			to: at("2025-01-03"),
+			..GameFilter::default()
+		};
+		assert_eq!(find(&between), [2]);
+		let carol = GameFilter {
+			guest_id: Some(guests[2].id),
+			..GameFilter::default()
+		};
+		assert_eq!(find(&carol), [2, 3]);
+		let alice_from = GameFilter {
+			guest_id: Some(guests[0].id),
+			..from
+		};
+		assert_eq!(find(&alice_from), [2]);
 
-	Ok(())
+		let page = |asc, limit, offset| {
+			ids(&find_games(&tx, room.id, &GameFilter::default(), asc, limit, offset).unwrap())
+		};
+		assert_eq!(page(false, 10, 0), [3, 2, 1]);
+		assert_eq!(page(true, 2, 0), [1, 2]);
+		assert_eq!(page(true, 2, 2), [3]);
+		assert_eq!(page(false, 2, 1), [2, 1]);
+		assert!(page(true, 2, 3).is_empty());
+	}
 }
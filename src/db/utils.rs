@@ -3,15 +3,15 @@ use rusqlite::{Connection, OptionalExtension, Transaction};
 
 /// Open connection to the database
 pub fn open_connection() -> Result<Connection> {
-	if cfg!(test) {
-		Connection::open("db/test.db3").map_err(std::convert::Into::into)
-	} else {
-		Connection::open("db/db.db3").map_err(std::convert::Into::into)
-	}
+	#[cfg(test)]
+	let path = TEST_DB.with(|db| db.0.clone());
+	#[cfg(not(test))]
+	let path = "db/db.db3";
+	Connection::open(path).map_err(std::convert::Into::into)
 }
 
 /// Convenience function to create a new transaction and map error
-pub fn new_transaction(conn: &mut Connection) -> Result<Transaction> {
+pub fn new_transaction(conn: &mut Connection) -> Result<Transaction<'_>> {
 	conn.transaction().map_err(std::convert::Into::into)
 }
 
@@ -28,12 +28,27 @@ pub fn commit(tx: Transaction) -> Result<()> {
 	tx.commit().map_err(std::convert::Into::into)
 }
 
+#[cfg(test)]
+thread_local! {
+	/// In-memory database of the test running on the thread, kept alive by the
+	/// connection while other connections come and go
+	static TEST_DB: (String, Connection) = {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		static NEXT: AtomicUsize = AtomicUsize::new(0);
+		let path = format!(
+			"file:test{}?mode=memory&cache=shared",
+			NEXT.fetch_add(1, Ordering::Relaxed)
+		);
+		let conn = Connection::open(&path).unwrap();
+		(path, conn)
+	};
+}
+
+/// Create the tables in a new database for the test
 #[cfg(test)]
 pub fn init() {
-	std::process::Command::new("./db/init.sh")
-		.arg("test")
-		.output()
-		.unwrap();
+	TEST_DB.with(|db| db.1.execute_batch(include_str!("../../db/db.sql")).unwrap());
 }
 
 pub(crate) fn max_id(tx: &Transaction, table: &str) -> Result<usize> {
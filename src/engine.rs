@@ -0,0 +1,7 @@
+pub mod action;
+pub mod event;
+pub mod state;
+
+pub use action::*;
+pub use event::*;
+pub use state::*;
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, bad_request_error};
+
+/// Action of a player, chips are computed by the engine
+///
+/// Every action but [`PlayerAction::Show`] is taken by the current player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerAction {
+	Check,
+	Call,
+	/// Bet the amount when nobody has bet in this round
+	Bet(usize),
+	/// Raise the bet of this round to the amount
+	RaiseTo(usize),
+	AllIn,
+	Fold,
+	/// Show hole cards after the game is over
+	Show,
+}
+
+impl PlayerAction {
+	/// Parse an action with its amount, only `bet` and `raise_to` take an amount
+	///
+	/// # Errors
+	///
+	/// Return bad request error if the action is unknown or the amount is
+	/// missing or unexpected
+	pub fn parse(action: &str, amount: Option<usize>) -> Result<Self> {
+		let action = match (action, amount) {
+			("check", None) => Self::Check,
+			("call", None) => Self::Call,
+			("bet", Some(amount)) => Self::Bet(amount),
+			("raise_to", Some(amount)) => Self::RaiseTo(amount),
+			("all_in", None) => Self::AllIn,
+			("fold", None) => Self::Fold,
+			("show", None) => Self::Show,
+			("bet" | "raise_to", None) => {
+				return Err(bad_request_error(format!("{action} needs an amount")));
+			}
+			("check" | "call" | "all_in" | "fold" | "show", Some(_)) => {
+				return Err(bad_request_error(format!("{action} takes no amount")));
+			}
+			_ => return Err(bad_request_error(format!("unknown action: {action}"))),
+		};
+
+		Ok(action)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(
+			PlayerAction::parse("call", None).unwrap(),
+			PlayerAction::Call
+		);
+		assert_eq!(
+			PlayerAction::parse("raise_to", Some(8)).unwrap(),
+			PlayerAction::RaiseTo(8)
+		);
+		assert!(PlayerAction::parse("raise_to", None).is_err());
+		assert!(PlayerAction::parse("fold", Some(1)).is_err());
+		assert!(PlayerAction::parse("raise", Some(8)).is_err());
+	}
+}
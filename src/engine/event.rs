@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	Action, Card, GameResult, Guest, PotResult, Record, Round,
+	equity::{PlayerEquity, StreetEquity},
+};
+
+use super::GameState;
+
+/// Something happened in a game, returned by the engine to be persisted
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Event {
+	/// An action or a card deal, the sequence number is not assigned yet
+	Record(Record),
+	/// The game goes to a new round
+	Round(Round),
+	/// Equities of the players in an all-in runout
+	Equity(StreetEquity),
+	/// The game is over and pots are awarded
+	Over {
+		results: Vec<GameResult>,
+		pots: Vec<PotResult>,
+	},
+}
+
+/// Event pushed to the clients watching a room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomEvent {
+	SeatJoined {
+		position: usize,
+		guest: Guest,
+	},
+	ReadyChanged {
+		guest_id: usize,
+		ready: bool,
+	},
+	HandStarted {
+		game_id: usize,
+		button: usize,
+		sb: usize,
+		bb: usize,
+	},
+	/// Hole cards of a player, only sent to the player
+	HoleCards {
+		game_id: usize,
+		guest_id: usize,
+		cards: Vec<Card>,
+	},
+	ActionTaken {
+		record: Record,
+	},
+	RoundChanged {
+		game_id: usize,
+		round: Round,
+	},
+	StreetDealt {
+		game_id: usize,
+		round: Round,
+		cards: Vec<Card>,
+	},
+	/// The player at `position` should act now
+	Turn {
+		game_id: usize,
+		position: usize,
+		guest_id: usize,
+	},
+	/// IDs of the guests who showed at showdown and their hole cards in the
+	/// showdown order, mucked hands are left out
+	Showdown {
+		game_id: usize,
+		hands: Vec<(usize, Vec<Card>)>,
+	},
+	/// Equities of the players left once a street is dealt in an all-in
+	/// runout
+	Equity {
+		game_id: usize,
+		round: Round,
+		players: Vec<PlayerEquity>,
+	},
+	Results {
+		game_id: usize,
+		results: Vec<GameResult>,
+		pots: Vec<PotResult>,
+	},
+}
+
+/// A room event with its position in the event log of the room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeqEvent {
+	/// Increases by one for each event of the room, starting from 1
+	pub seq: usize,
+	#[serde(flatten)]
+	pub event: RoomEvent,
+}
+
+impl RoomEvent {
+	/// Events for the clients from the events returned by the engine
+	///
+	/// Records in `events` should be persisted first so that they have their
+	/// sequence numbers.
+	#[must_use]
+	pub fn from_game(state: &GameState, events: &[Event]) -> Vec<Self> {
+		let game_id = state.game.id;
+		let mut room_events = Vec::new();
+
+		for event in events {
+			match event {
+				Event::Record(record) if record.is_hole_cards() => {
+					room_events.push(Self::HoleCards {
+						game_id,
+						guest_id: record.guest_id.unwrap(),
+						cards: record.cards.clone(),
+					});
+				}
+				Event::Record(record) if record.action == Action::Deal => {
+					room_events.push(Self::StreetDealt {
+						game_id,
+						round: record.round,
+						cards: record.cards.clone(),
+					});
+				}
+				Event::Record(record) => room_events.push(Self::ActionTaken {
+					record: record.clone(),
+				}),
+				&Event::Round(round) => room_events.push(Self::RoundChanged { game_id, round }),
+				Event::Equity(equity) => room_events.push(Self::Equity {
+					game_id,
+					round: equity.round,
+					players: equity.players.clone(),
+				}),
+				Event::Over { results, pots } => {
+					if state.room.player_count() > 1 {
+						let hands = events
+							.iter()
+							.filter_map(|e| match e {
+								Event::Record(record) if record.action == Action::Show => {
+									Some((record.guest_id.unwrap(), record.cards.clone()))
+								}
+								_ => None,
+							})
+							.collect();
+						room_events.push(Self::Showdown { game_id, hands });
+					}
+					room_events.push(Self::Results {
+						game_id,
+						results: results.clone(),
+						pots: pots.clone(),
+					});
+				}
+			}
+		}
+
+		if let Some(guest) = state.current_guest()
+			&& !state.game.is_over()
+		{
+			room_events.push(Self::Turn {
+				game_id,
+				position: state.game.position,
+				guest_id: guest.id,
+			});
+		}
+
+		room_events
+	}
+
+	/// The only guest who may see this event, None if everyone may
+	#[must_use]
+	pub fn owner(&self) -> Option<usize> {
+		match self {
+			Self::HoleCards { guest_id, .. } => Some(*guest_id),
+			_ => None,
+		}
+	}
+
+	/// Whether the guest may see this event, None for anonymous viewers
+	#[must_use]
+	pub fn visible_to(&self, guest_id: Option<usize>) -> bool {
+		self.owner().is_none_or(|owner| guest_id == Some(owner))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Card, Room};
+
+	#[test]
+	fn test_from_game() {
+		let mut room = Room::new(1);
+		room.insert(Guest::new(1, "Alice")).unwrap();
+		room.insert(Guest::new(2, "Bob")).unwrap();
+		let (state, events) = GameState::start(room, 1, Card::new_sorted());
+
+		let room_events = RoomEvent::from_game(&state, &events);
+		let hole_cards: Vec<_> = room_events
+			.iter()
+			.filter(|e| matches!(e, RoomEvent::HoleCards { .. }))
+			.collect();
+		assert_eq!(hole_cards.len(), 2);
+		// hole cards are only visible to their owner
+		assert!(hole_cards[0].visible_to(Some(1)));
+		assert!(!hole_cards[0].visible_to(Some(2)));
+		assert!(!hole_cards[0].visible_to(None));
+		assert!(matches!(
+			room_events.last(),
+			Some(RoomEvent::Turn { guest_id: 2, .. })
+		));
+	}
+}
@@ -0,0 +1,1316 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	Action, Card, CardSet, Deck, Game, GameResult, Guest, Pot, PotResult, Record, Room, Round,
+	equity::{EquityOptions, PlayerEquity, StreetEquity, calc_equity},
+	error::{Result, bad_request_error, conflict_error, forbidden_error},
+	eval::evaluate_set,
+};
+
+use super::{Event, PlayerAction};
+
+/// Everything needed to run a game, the rules live here and nowhere else
+///
+/// The state is changed only by [`GameState::start`] and
+/// [`GameState::apply`], both return the events which happened so that the
+/// caller can persist them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameState {
+	pub room: Room,
+	pub game: Game,
+	/// Hole cards of each seat
+	pub hands: [Option<Vec<Card>>; Room::MAX_SEATS],
+	/// All five common cards, including those not dealt yet
+	pub board: Vec<Card>,
+	/// Whether hole cards of each seat have been shown
+	pub shown: [bool; Room::MAX_SEATS],
+}
+
+impl GameState {
+	/// Start a new game in the room
+	///
+	/// The button is passed, cards are dealt from the end of `deck`, then antes
+	/// and blinds are posted.
+	///
+	/// # Panics
+	///
+	/// Will panic if there are less than two guests in the room or not enough
+	/// cards in `deck`
+	#[must_use]
+	pub fn start(mut room: Room, game_id: usize, mut deck: Deck) -> (Self, Vec<Event>) {
+		assert!(room.count() >= 2);
+		room.pass_button();
+		let game = Game::new(game_id, room.id, room.sb, room.settings.big_blind);
+
+		let mut hands = [const { None }; Room::MAX_SEATS];
+		for (position, seat) in room.seats.iter_mut().enumerate() {
+			if let Some(seat) = seat {
+				seat.bet = 0;
+				seat.round_bet = 0;
+				seat.fold = false;
+				hands[position] = Some(vec![deck.pop().unwrap(), deck.pop().unwrap()]);
+			}
+		}
+		let board = (0..5).map(|_| deck.pop().unwrap()).collect();
+
+		let mut state = Self {
+			room,
+			game,
+			hands,
+			board,
+			shown: [false; Room::MAX_SEATS],
+		};
+		let mut events = Vec::new();
+
+		let settings = state.room.settings.clone();
+		if settings.ante > 0 {
+			if settings.big_blind_ante {
+				state.post(state.room.bb, Action::Ante, settings.ante, &mut events);
+			} else {
+				for position in 0..Room::MAX_SEATS {
+					if state.room.seats[position].is_some() {
+						state.post(position, Action::Ante, settings.ante, &mut events);
+					}
+				}
+			}
+		}
+		state.post(
+			state.room.sb,
+			Action::SmallBlind,
+			settings.small_blind,
+			&mut events,
+		);
+		state.post(
+			state.room.bb,
+			Action::BigBlind,
+			settings.big_blind,
+			&mut events,
+		);
+
+		for (position, hand) in state.hands.iter().enumerate() {
+			if let Some(hand) = hand {
+				let guest_id = state.room.get_guest(position).unwrap().id;
+				events.push(Event::Record(Record::deal_hand(
+					&state.game,
+					position,
+					guest_id,
+					hand.clone(),
+				)));
+			}
+		}
+
+		state.game.position = state.room.bb;
+		state.pass();
+		// blinds and antes may have put players all-in
+		state.update_round(&mut events);
+
+		(state, events)
+	}
+
+	/// The guest who should act now
+	#[must_use]
+	pub fn current_guest(&self) -> Option<&Guest> {
+		self.room.get_guest(self.game.position)
+	}
+
+	/// Common cards dealt so far
+	#[must_use]
+	pub fn common(&self) -> &[Card] {
+		match self.game.round {
+			Round::PreFlop => &[],
+			Round::Flop => &self.board[..3],
+			Round::Turn => &self.board[..4],
+			Round::River | Round::Over => &self.board,
+		}
+	}
+
+	/// Apply an action of the current player
+	///
+	/// # Errors
+	///
+	/// Return forbidden error if the game is over, or bad request error if the
+	/// action is not allowed, the state is unchanged in both cases
+	pub fn apply(&mut self, action: PlayerAction) -> Result<Vec<Event>> {
+		if self.game.is_over() {
+			return Err(forbidden_error("game is already finished"));
+		}
+
+		let mut events = Vec::new();
+		match action {
+			PlayerAction::Fold => self.fold(&mut events),
+			PlayerAction::Show => {
+				return Err(bad_request_error(
+					"hand can only be shown after the game is over",
+				));
+			}
+			_ => {
+				let chips = self.chips(action)?;
+				self.put(chips, &mut events)?;
+			}
+		}
+		self.pass();
+		self.update_round(&mut events);
+
+		Ok(events)
+	}
+
+	/// The action putting in `chips` as the current player
+	///
+	/// For clients sending chips only, the action is validated by
+	/// [`GameState::apply`] like any other.
+	///
+	/// # Panics
+	///
+	/// Will panic if the game is over
+	#[must_use]
+	pub fn action_for_chips(&self, chips: usize) -> PlayerAction {
+		assert!(!self.game.is_over());
+		let position = self.game.position;
+		let seat = self.room.seats[position].as_ref().unwrap();
+		let max_bet = self.room.max_bet();
+
+		if chips == seat.stack {
+			PlayerAction::AllIn
+		} else if chips == 0 {
+			PlayerAction::Check
+		} else if chips == self.room.call_amount(position) {
+			PlayerAction::Call
+		} else if max_bet == 0 {
+			PlayerAction::Bet(chips)
+		} else {
+			PlayerAction::RaiseTo(seat.round_bet + chips)
+		}
+	}
+
+	/// Show hole cards of the guest after the game is over
+	///
+	/// # Errors
+	///
+	/// Return forbidden error if the game is not over or the guest is not in
+	/// the game, or conflict error if the hand has been shown
+	pub fn show(&mut self, guest_id: usize) -> Result<Vec<Event>> {
+		if !self.game.is_over() {
+			return Err(forbidden_error(
+				"hand can only be shown after the game is over",
+			));
+		}
+		let position = (0..Room::MAX_SEATS)
+			.find(|&p| {
+				self.hands[p].is_some() && self.room.get_guest(p).is_some_and(|g| g.id == guest_id)
+			})
+			.ok_or(forbidden_error("guest not in the game"))?;
+		if self.shown[position] {
+			return Err(conflict_error("hand is already shown"));
+		}
+
+		self.shown[position] = true;
+		Ok(vec![Event::Record(Record::show_hand(
+			&self.game,
+			position,
+			guest_id,
+			self.hands[position].clone().unwrap(),
+		))])
+	}
+
+	/// Positions of the players left at showdown in the order to show hands
+	///
+	/// The last aggressor shows first, or the first player after the button if
+	/// everyone checked the last betting round, then clockwise.
+	#[must_use]
+	pub fn showdown_order(&self) -> Vec<usize> {
+		let first = self.game.aggressor.unwrap_or(self.room.button + 1);
+		(0..Room::MAX_SEATS)
+			.map(|i| (first + i) % Room::MAX_SEATS)
+			.filter(|&p| self.room.seats[p].as_ref().is_some_and(|s| !s.fold))
+			.collect()
+	}
+
+	/// Post an ante or a blind for the player at `position`
+	///
+	/// Antes are dead chips, they go into the pot but don't count as bets of
+	/// the round. The player goes all-in if the stack is less than the chips.
+	fn post(&mut self, position: usize, action: Action, chips: usize, events: &mut Vec<Event>) {
+		let seat = self.room.seats[position].as_mut().unwrap();
+		let chips = chips.min(seat.stack);
+
+		seat.stack -= chips;
+		seat.bet += chips;
+		if action != Action::Ante {
+			seat.round_bet += chips;
+		}
+		self.game.pot += chips;
+		events.push(Event::Record(Record::player_action(
+			&self.game,
+			position,
+			seat.guest.id,
+			action,
+			chips,
+		)));
+	}
+
+	/// Chips the current player puts in by the action
+	///
+	/// # Errors
+	///
+	/// Return bad request error if the action doesn't fit the current bets,
+	/// e.g. checking when there is a bet to call
+	fn chips(&self, action: PlayerAction) -> Result<usize> {
+		let legal = self.game.legal_actions(&self.room).unwrap();
+		let seat = self.room.seats[legal.position].as_ref().unwrap();
+		let max_bet = self.room.max_bet();
+
+		match action {
+			PlayerAction::Check if !legal.check => Err(bad_request_error(format!(
+				"can't check, {} to call",
+				legal.call.unwrap()
+			))),
+			PlayerAction::Check => Ok(0),
+			PlayerAction::Call => legal
+				.call
+				.ok_or_else(|| bad_request_error("nothing to call, check instead")),
+			PlayerAction::Bet(_) if max_bet > 0 => {
+				Err(bad_request_error("can't bet, raise instead"))
+			}
+			PlayerAction::Bet(0) => Err(bad_request_error("bet should be positive")),
+			PlayerAction::Bet(amount) => Ok(amount),
+			PlayerAction::RaiseTo(_) if max_bet == 0 => {
+				Err(bad_request_error("nothing to raise, bet instead"))
+			}
+			PlayerAction::RaiseTo(amount) if amount <= max_bet => Err(bad_request_error(format!(
+				"should raise to more than {max_bet}"
+			))),
+			PlayerAction::RaiseTo(amount) => Ok(amount - seat.round_bet),
+			PlayerAction::AllIn => legal.all_in.ok_or_else(|| {
+				bad_request_error(
+					"action is not reopened by a short all-in, you can only call or fold",
+				)
+			}),
+			PlayerAction::Fold | PlayerAction::Show => unreachable!("no chips are put in"),
+		}
+	}
+
+	/// Put chips in as the current player
+	///
+	/// `chips` are the chips put in by this action. Unless the player goes
+	/// all-in, the bet must either call the max bet of the round or raise it by
+	/// at least the size of the last full bet or raise. A short all-in raise does
+	/// not reopen the action to players who have already acted.
+	fn put(&mut self, chips: usize, events: &mut Vec<Event>) -> Result<()> {
+		let max_bet = self.room.max_bet();
+		let game = &mut self.game;
+		let seat = self.room.seats[game.position].as_mut().unwrap();
+
+		if chips > seat.stack {
+			return Err(bad_request_error(format!(
+				"not enough chips, your stack is {}",
+				seat.stack
+			)));
+		}
+		let allin = chips == seat.stack;
+		let round_bet = seat.round_bet + chips;
+
+		if round_bet > max_bet {
+			if game.has_acted(game.position) {
+				return Err(bad_request_error(
+					"action is not reopened by a short all-in, you can only call or fold",
+				));
+			}
+
+			let raise = round_bet - max_bet;
+			if raise >= game.min_raise {
+				game.min_raise = raise;
+				game.acted = 0;
+			} else if !allin {
+				return Err(bad_request_error(format!(
+					"raise too small, should raise to at least {}",
+					max_bet + game.min_raise
+				)));
+			}
+			game.aggressor = Some(game.position);
+		} else if round_bet < max_bet && !allin {
+			return Err(bad_request_error(format!(
+				"should bet more, {} to call",
+				max_bet - seat.round_bet
+			)));
+		}
+
+		let action = if allin {
+			Action::AllIn
+		} else if chips == 0 {
+			Action::Check
+		} else if round_bet == max_bet {
+			Action::Call
+		} else if max_bet == 0 {
+			Action::Bet
+		} else {
+			Action::Raise
+		};
+		events.push(Event::Record(Record::action(
+			game,
+			seat.guest.id,
+			action,
+			chips,
+		)));
+
+		seat.stack -= chips;
+		seat.bet += chips;
+		seat.round_bet += chips;
+		game.pot += chips;
+		game.set_acted(game.position);
+
+		Ok(())
+	}
+
+	/// Fold as the current player
+	fn fold(&mut self, events: &mut Vec<Event>) {
+		let seat = self.room.seats[self.game.position].as_mut().unwrap();
+		seat.fold = true;
+		events.push(Event::Record(Record::action(
+			&self.game,
+			seat.guest.id,
+			Action::Fold,
+			0,
+		)));
+		self.game.set_acted(self.game.position);
+	}
+
+	/// Correct the current position to a player who can still act
+	fn correct(&mut self) {
+		for i in 0..Room::MAX_SEATS {
+			let p = (self.game.position + i) % Room::MAX_SEATS;
+			if let Some(seat) = &self.room.seats[p] {
+				if seat.fold || seat.allin() {
+					continue;
+				}
+				self.game.position = p;
+				return;
+			}
+		}
+	}
+
+	/// Pass control to the next player
+	fn pass(&mut self) {
+		self.game.position += 1;
+		self.correct();
+	}
+
+	/// Whether the betting of the current round is closed
+	///
+	/// A round is closed when every player who can still act has matched the
+	/// max bet of the round and acted since the last full raise. Blinds are not
+	/// actions, so the big blind still has the option in a limped pot. A player
+	/// left alone against all-in players only needs to match the max bet.
+	#[must_use]
+	pub fn is_round_closed(&self) -> bool {
+		let max_bet = self.room.max_bet();
+		let active_count = self.room.active_count();
+
+		self.room
+			.seats
+			.iter()
+			.enumerate()
+			.all(|(i, seat)| match seat {
+				Some(seat) if !seat.fold && !seat.allin() => {
+					seat.round_bet == max_bet && (active_count == 1 || self.game.has_acted(i))
+				}
+				_ => true,
+			})
+	}
+
+	/// Go to the next round if the betting is closed
+	///
+	/// Common cards of the new round are dealt, the rest of the board runs out
+	/// if the game is over with more than one player.
+	fn update_round(&mut self, events: &mut Vec<Event>) {
+		let last_round = self.game.round;
+
+		if self.room.player_count() == 1 {
+			// all fold except one
+			self.game.round = Round::Over;
+		} else if self.is_round_closed() {
+			// no more betting if less than two players can act
+			self.game.round = if self.room.active_count() < 2 {
+				Round::Over
+			} else {
+				self.game.round.next_round()
+			};
+			self.game.min_raise = self.room.settings.big_blind;
+			self.game.acted = 0;
+			// the aggressor of the last betting round shows first
+			if !self.game.is_over() {
+				self.game.aggressor = None;
+			}
+			self.game.position = self.room.button + 1;
+			self.correct();
+		} else {
+			return;
+		}
+
+		self.room.new_round();
+		events.push(Event::Round(self.game.round));
+
+		if !self.game.is_over() || self.room.player_count() > 1 {
+			// everyone left is all-in, show the odds street by street
+			let runout = self.game.is_over() && last_round < Round::River;
+			if runout {
+				events.push(Event::Equity(self.street_equity(last_round)));
+			}
+			let mut round = last_round;
+			while round < Round::River && round < self.game.round {
+				round = round.next_round();
+				let cards = match round {
+					Round::Flop => self.board[..3].to_vec(),
+					Round::Turn => vec![self.board[3]],
+					_ => vec![self.board[4]],
+				};
+				events.push(Event::Record(Record::deal_common(&self.game, round, cards)));
+				if runout {
+					events.push(Event::Equity(self.street_equity(round)));
+				}
+			}
+		}
+
+		if self.game.is_over() {
+			self.showdown(events);
+		}
+	}
+
+	/// Equities of the players left with the common cards dealt by `round`
+	fn street_equity(&self, round: Round) -> StreetEquity {
+		let dealt = match round {
+			Round::PreFlop => 0,
+			Round::Flop => 3,
+			Round::Turn => 4,
+			Round::River | Round::Over => 5,
+		};
+		let positions: Vec<_> = (0..Room::MAX_SEATS)
+			.filter(|&p| self.room.seats[p].as_ref().is_some_and(|s| !s.fold))
+			.collect();
+		let hands: Vec<_> = positions
+			.iter()
+			.map(|&p| self.hands[p].clone().unwrap())
+			.collect();
+		// sampled preflop within about half a percent, and fast enough to run
+		// within a request
+		let options = EquityOptions {
+			max_exhaustive: 20_000,
+			samples: 20_000,
+			seed: self.game.id as u64,
+		};
+		let result = calc_equity(&hands, &self.board[..dealt], &[], &options).unwrap();
+
+		StreetEquity {
+			game_id: self.game.id,
+			round,
+			players: positions
+				.iter()
+				.zip(result.players)
+				.map(|(&position, equity)| PlayerEquity {
+					guest_id: self.room.get_guest(position).unwrap().id,
+					position,
+					equity,
+				})
+				.collect(),
+		}
+	}
+
+	/// Award the pots and settle stacks and bankrolls
+	///
+	/// Every pot built by [`Pot::build`] is awarded only to its eligible
+	/// players, ties are split evenly among the winners, see
+	/// [`Room::split_pot`] for how odd chips are awarded.
+	///
+	/// If more than one player is left, hands are revealed in
+	/// [`GameState::showdown_order`]. A hand is shown if it wins chips or is not
+	/// beaten by the hands shown before it, otherwise it is mucked. Every hand
+	/// is shown if anyone is all-in.
+	fn showdown(&mut self, events: &mut Vec<Event>) {
+		let order = self.showdown_order();
+		let room = &mut self.room;
+		let (pots, uncalled) = Pot::build(&room.contributions());
+
+		// strengths of the best hands
+		let mut hands = [None; Room::MAX_SEATS];
+		if room.player_count() > 1 {
+			for (p, seat) in room.seats.iter().enumerate() {
+				if let Some(seat) = seat
+					&& !seat.fold
+				{
+					let mut cards = CardSet::from(&self.board[..]);
+					cards.extend(self.hands[p].iter().flatten().copied());
+					hands[p] = Some(evaluate_set(cards));
+				}
+			}
+		}
+
+		let mut won = [0; Room::MAX_SEATS];
+		let mut winning = [false; Room::MAX_SEATS];
+		if let Some(uncalled) = uncalled {
+			won[uncalled.position] += uncalled.chips;
+		}
+		let mut pot_results = Vec::new();
+		for (number, pot) in pots.iter().enumerate() {
+			let mut winners = Vec::new();
+			for &p in &pot.eligible {
+				match winners.first().map(|&w: &usize| hands[p].cmp(&hands[w])) {
+					None | Some(Ordering::Equal) => winners.push(p),
+					Some(Ordering::Greater) => winners = vec![p],
+					Some(Ordering::Less) => (),
+				}
+			}
+
+			let chips = room.split_pot(pot.chips, &winners);
+			for (&p, &chips) in winners.iter().zip(&chips) {
+				won[p] += chips;
+				winning[p] = true;
+			}
+			pot_results.push(PotResult {
+				game_id: self.game.id,
+				number,
+				chips: pot.chips,
+				eligible: pot
+					.eligible
+					.iter()
+					.map(|&p| room.get_guest(p).unwrap().id)
+					.collect(),
+				winners: winners
+					.iter()
+					.zip(chips)
+					.map(|(&p, chips)| (room.get_guest(p).unwrap().id, chips))
+					.collect(),
+			});
+		}
+
+		if room.player_count() > 1 {
+			let allin = room.seats.iter().flatten().any(|s| !s.fold && s.allin());
+			let mut best = None;
+			for p in order {
+				let hand = hands[p];
+				let guest_id = room.get_guest(p).unwrap().id;
+				if allin || winning[p] || best <= hand {
+					best = best.max(hand);
+					self.shown[p] = true;
+					events.push(Event::Record(Record::show_hand(
+						&self.game,
+						p,
+						guest_id,
+						self.hands[p].clone().unwrap(),
+					)));
+				} else {
+					events.push(Event::Record(Record::player_action(
+						&self.game,
+						p,
+						guest_id,
+						Action::Muck,
+						0,
+					)));
+				}
+			}
+		}
+
+		let mut results = Vec::new();
+		for (p, seat) in room
+			.seats
+			.iter_mut()
+			.enumerate()
+			.filter_map(|(p, s)| s.as_mut().map(|s| (p, s)))
+		{
+			let returned = uncalled.filter(|u| u.position == p).map_or(0, |u| u.chips);
+			let diff = won[p] as isize - seat.bet as isize;
+			seat.stack += won[p];
+			seat.guest.bankroll += diff;
+			seat.ready = false;
+			results.push(GameResult::new(
+				self.game.id,
+				seat.guest.id,
+				diff,
+				won[p] > returned,
+				returned,
+			));
+		}
+
+		events.push(Event::Over {
+			results,
+			pots: pot_results,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{Rng, SeedableRng, rngs::StdRng};
+
+	use super::*;
+	use crate::{Hand, LegalActions, RoomSettings};
+
+	/// Deck dealing `hands` to seats in order, then the `board`
+	fn deck(hands: &[&str], board: &str) -> Deck {
+		let mut deck: Deck = hands
+			.iter()
+			.chain(&[board])
+			.flat_map(|cards| cards.split(' '))
+			.map(Card::parse)
+			.collect();
+		deck.reverse();
+		deck
+	}
+
+	/// Start a game with `stacks.len()` players sitting from seat 0, the
+	/// button is on the last seat, except heads-up where it is on seat 1
+	fn start_with(stacks: &[usize], settings: RoomSettings, deck: Deck) -> (GameState, Vec<Event>) {
+		let mut room = Room::new(1);
+		room.settings = settings;
+		for (i, &stack) in stacks.iter().enumerate() {
+			room.insert(Guest::new(i + 1, "guest")).unwrap();
+			room.seats[i].as_mut().unwrap().stack = stack;
+		}
+		room.button = stacks.len() - 2;
+		GameState::start(room, 1, deck)
+	}
+
+	fn start(stacks: &[usize]) -> GameState {
+		start_with(stacks, RoomSettings::default(), Card::new_sorted()).0
+	}
+
+	fn round_changed(events: &[Event]) -> bool {
+		events.iter().any(|e| matches!(e, Event::Round(_)))
+	}
+
+	/// Put chips in as the current player, return whether the round changed
+	fn act(state: &mut GameState, chips: usize) -> bool {
+		let action = state.action_for_chips(chips);
+		round_changed(&state.apply(action).unwrap())
+	}
+
+	fn fold(state: &mut GameState) -> bool {
+		round_changed(&state.apply(PlayerAction::Fold).unwrap())
+	}
+
+	fn records(events: &[Event]) -> Vec<(Option<usize>, Action, usize)> {
+		events
+			.iter()
+			.filter_map(|e| match e {
+				Event::Record(r) => Some((r.position, r.action, r.chips)),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn stack(state: &GameState, position: usize) -> usize {
+		state.room.seats[position].as_ref().unwrap().stack
+	}
+
+	#[test]
+	fn test_start() {
+		let (state, events) = start_with(
+			&[100, 100, 100],
+			RoomSettings::default(),
+			deck(&["SA HA", "SK HK", "SQ HQ"], "C2 D7 C9 S3 H8"),
+		);
+		assert_eq!((state.room.button, state.room.sb, state.room.bb), (2, 0, 1));
+		assert_eq!(state.game.pot, 3);
+		assert_eq!(state.game.position, 2);
+		assert_eq!(
+			state.hands[1],
+			Some(vec![Card::parse("SK"), Card::parse("HK")])
+		);
+		assert!(state.common().is_empty());
+		assert_eq!(
+			records(&events),
+			vec![
+				(Some(0), Action::SmallBlind, 1),
+				(Some(1), Action::BigBlind, 2),
+				(Some(0), Action::Deal, 0),
+				(Some(1), Action::Deal, 0),
+				(Some(2), Action::Deal, 0),
+			]
+		);
+	}
+
+	#[test]
+	fn test_antes() {
+		let settings = RoomSettings {
+			ante: 1,
+			..RoomSettings::default()
+		};
+		let (state, events) = start_with(&[100, 100, 100], settings.clone(), Card::new_sorted());
+		assert_eq!(state.game.pot, 6);
+		// antes are dead chips
+		assert_eq!(state.room.max_bet(), 2);
+		assert_eq!(state.room.call_amount(0), 1);
+		let antes = records(&events)
+			.into_iter()
+			.filter(|r| r.1 == Action::Ante)
+			.count();
+		assert_eq!(antes, 3);
+
+		let settings = RoomSettings {
+			ante: 2,
+			big_blind_ante: true,
+			..settings
+		};
+		let (state, events) = start_with(&[100, 100, 100], settings, Card::new_sorted());
+		assert_eq!(state.game.pot, 5);
+		assert_eq!(stack(&state, 1), 96);
+		assert_eq!(records(&events)[0], (Some(1), Action::Ante, 2));
+	}
+
+	#[test]
+	fn test_big_blind_option() {
+		let mut state = start(&[100, 100, 100]);
+
+		assert!(!act(&mut state, 2));
+		assert!(!act(&mut state, 1));
+		// big blind has the option
+		assert_eq!(state.game.position, 1);
+		assert!(act(&mut state, 0));
+		assert_eq!(state.game.round, Round::Flop);
+		assert_eq!(state.game.position, 0);
+		assert_eq!(state.common().len(), 3);
+	}
+
+	#[test]
+	fn test_big_blind_raise() {
+		let mut state = start(&[100, 100, 100]);
+
+		assert!(!act(&mut state, 2));
+		assert!(!act(&mut state, 1));
+		assert!(!act(&mut state, 4));
+		assert!(!act(&mut state, 4));
+		assert_eq!(state.game.round, Round::PreFlop);
+		assert_eq!(state.game.position, 0);
+		assert!(act(&mut state, 4));
+		assert_eq!(state.game.round, Round::Flop);
+	}
+
+	#[test]
+	fn test_checked_round() {
+		let mut state = start(&[100, 100, 100]);
+		for chips in [2, 1, 0] {
+			act(&mut state, chips);
+		}
+		assert_eq!(state.game.round, Round::Flop);
+
+		assert!(!act(&mut state, 0));
+		assert!(!act(&mut state, 0));
+		let events = state.apply(PlayerAction::Check).unwrap();
+		assert_eq!(state.game.round, Round::Turn);
+		assert_eq!(
+			records(&events),
+			vec![(Some(2), Action::Check, 0), (None, Action::Deal, 0)]
+		);
+	}
+
+	#[test]
+	fn test_folded_players() {
+		let mut state = start(&[100, 100, 100, 100]);
+
+		assert!(!fold(&mut state));
+		assert!(!act(&mut state, 6));
+		assert!(!fold(&mut state));
+		assert!(act(&mut state, 4));
+		assert_eq!(state.game.round, Round::Flop);
+		// small blind folded, big blind acts first
+		assert_eq!(state.game.position, 1);
+
+		assert!(!act(&mut state, 0));
+		assert!(fold(&mut state));
+		assert!(state.game.is_over());
+	}
+
+	#[test]
+	fn test_heads_up() {
+		let mut state = start(&[100, 100]);
+		// the button posts the small blind and acts first
+		assert_eq!(state.game.position, 1);
+
+		assert!(!act(&mut state, 1));
+		assert_eq!(state.game.position, 0);
+		assert!(act(&mut state, 0));
+		assert_eq!(state.game.round, Round::Flop);
+		// the big blind acts first after preflop
+		assert_eq!(state.game.position, 0);
+		assert!(!act(&mut state, 0));
+		assert!(act(&mut state, 0));
+		assert_eq!(state.game.round, Round::Turn);
+	}
+
+	#[test]
+	fn test_allin_players() {
+		let mut state = start(&[100, 50, 100]);
+
+		assert!(!act(&mut state, 2));
+		assert!(!act(&mut state, 1));
+		assert!(!act(&mut state, 48));
+		assert_eq!(state.game.position, 2);
+		assert!(!act(&mut state, 48));
+		assert!(act(&mut state, 48));
+		assert_eq!(state.game.round, Round::Flop);
+		// the allin player is skipped
+		assert_eq!(state.game.position, 0);
+
+		assert!(!act(&mut state, 0));
+		assert!(act(&mut state, 0));
+		assert_eq!(state.game.round, Round::Turn);
+	}
+
+	#[test]
+	fn test_all_allin() {
+		let mut state = start(&[100, 100, 30]);
+
+		assert!(!act(&mut state, 30));
+		assert!(!act(&mut state, 99));
+		// a single player left needs to match the bet only
+		assert!(!state.game.is_over());
+		let events = state.apply(PlayerAction::Call).unwrap();
+		assert!(state.game.is_over());
+		// the board runs out
+		let deals = records(&events)
+			.into_iter()
+			.filter(|r| r.1 == Action::Deal)
+			.count();
+		assert_eq!(deals, 3);
+		assert_eq!(state.common().len(), 5);
+	}
+
+	#[test]
+	fn test_short_allin_not_closed() {
+		let mut state = start(&[100, 100, 5]);
+
+		assert!(!act(&mut state, 5));
+		assert!(!act(&mut state, 4));
+		assert!(act(&mut state, 3));
+		assert_eq!(state.game.round, Round::Flop);
+	}
+
+	#[test]
+	fn test_short_allin_not_reopened() {
+		let mut state = start(&[100, 100, 100, 7]);
+
+		// the button goes all-in for less than a full raise
+		act(&mut state, 6);
+		act(&mut state, 7);
+		// players yet to act can still raise
+		act(&mut state, 6);
+		act(&mut state, 5);
+		assert_eq!(state.game.position, 2);
+		let err = state.apply(PlayerAction::RaiseTo(17)).unwrap_err();
+		assert!(err.to_string().contains("not reopened"));
+		let legal = state.game.legal_actions(&state.room).unwrap();
+		assert_eq!(legal.all_in, None);
+		assert_offered_accepted(&state);
+		// calling is still allowed
+		assert!(act(&mut state, 1));
+	}
+
+	/// Every action offered, with the smallest and largest amounts
+	fn offered(legal: &LegalActions) -> Vec<PlayerAction> {
+		let mut actions = vec![PlayerAction::Fold];
+		if legal.check {
+			actions.push(PlayerAction::Check);
+		}
+		if legal.call.is_some() {
+			actions.push(PlayerAction::Call);
+		}
+		if let Some(bet) = legal.bet {
+			actions.extend([PlayerAction::Bet(bet.min), PlayerAction::Bet(bet.max)]);
+		}
+		if let Some(raise_to) = legal.raise_to {
+			actions.extend([
+				PlayerAction::RaiseTo(raise_to.min),
+				PlayerAction::RaiseTo(raise_to.max),
+			]);
+		}
+		if legal.all_in.is_some() {
+			actions.push(PlayerAction::AllIn);
+		}
+		actions
+	}
+
+	/// Assert that `apply` accepts every action offered to the current player
+	fn assert_offered_accepted(state: &GameState) -> Vec<PlayerAction> {
+		let legal = state.game.legal_actions(&state.room).unwrap();
+		let actions = offered(&legal);
+		for &action in &actions {
+			let mut copy: GameState =
+				serde_json::from_value(serde_json::to_value(state).unwrap()).unwrap();
+			assert!(
+				copy.apply(action).is_ok(),
+				"{action:?} is offered by {legal:?}"
+			);
+		}
+		actions
+	}
+
+	#[test]
+	fn test_legal_actions_accepted() {
+		let mut rng = StdRng::seed_from_u64(11);
+		for _ in 0..20 {
+			// a short stack goes all-in for less than a full raise
+			let mut stacks = vec![200; rng.random_range(2..=4)];
+			stacks.insert(rng.random_range(0..=stacks.len()), rng.random_range(3..=9));
+			let mut state = start_with(&stacks, RoomSettings::default(), Card::new_sorted()).0;
+
+			while !state.game.is_over() {
+				let actions = assert_offered_accepted(&state);
+				// play on with small bets to see more spots
+				let next: Vec<_> = actions
+					.into_iter()
+					.filter(|&a| {
+						let legal = state.game.legal_actions(&state.room).unwrap();
+						match a {
+							PlayerAction::Fold => false,
+							PlayerAction::Bet(chips) => legal.bet.unwrap().min == chips,
+							PlayerAction::RaiseTo(chips) => legal.raise_to.unwrap().min == chips,
+							PlayerAction::AllIn => state.room.seats[legal.position]
+								.as_ref()
+								.is_some_and(|s| s.stack < 10),
+							_ => true,
+						}
+					})
+					.collect();
+				let action = next[rng.random_range(0..next.len())];
+				state.apply(action).unwrap();
+			}
+		}
+	}
+
+	#[test]
+	fn test_invalid_actions() {
+		let mut state = start(&[100, 100, 100]);
+
+		for (action, reason) in [
+			(PlayerAction::Check, "2 to call"),
+			(PlayerAction::Bet(4), "raise instead"),
+			(PlayerAction::RaiseTo(2), "more than 2"),
+			(PlayerAction::RaiseTo(3), "raise too small"),
+			(PlayerAction::RaiseTo(101), "not enough chips"),
+		] {
+			let err = state.apply(action).unwrap_err();
+			assert!(err.to_string().contains(reason));
+			// the state is unchanged
+			assert_eq!(state.game.pot, 3);
+			assert_eq!(state.game.position, 2);
+			assert_eq!(stack(&state, 2), 100);
+		}
+
+		// raise to the min raise
+		assert!(!act(&mut state, 4));
+		let err = state.apply(PlayerAction::RaiseTo(5)).unwrap_err();
+		assert!(err.to_string().contains("at least 6"));
+		state.apply(PlayerAction::Call).unwrap();
+		state.apply(PlayerAction::Call).unwrap();
+		assert_eq!(state.game.round, Round::Flop);
+
+		let err = state.apply(PlayerAction::Call).unwrap_err();
+		assert!(err.to_string().contains("check instead"));
+		let err = state.apply(PlayerAction::RaiseTo(4)).unwrap_err();
+		assert!(err.to_string().contains("bet instead"));
+		assert!(state.apply(PlayerAction::Bet(0)).is_err());
+		assert!(state.apply(PlayerAction::Bet(1)).is_err());
+		state.apply(PlayerAction::Bet(2)).unwrap();
+		assert_eq!(state.room.max_bet(), 2);
+	}
+
+	#[test]
+	fn test_allin_blind() {
+		let (state, events) = start_with(&[100, 1], RoomSettings::default(), Card::new_sorted());
+
+		// no betting is possible
+		assert!(state.game.is_over());
+		assert_eq!(state.common().len(), 5);
+		assert!(events.iter().any(|e| matches!(e, Event::Over { .. })));
+		assert_eq!(stack(&state, 0) + stack(&state, 1), 101);
+	}
+
+	#[test]
+	fn test_apply_after_over() {
+		let mut state = start(&[100, 100]);
+		fold(&mut state);
+		assert!(state.game.is_over());
+		assert!(state.apply(PlayerAction::Check).is_err());
+		assert!(state.apply(PlayerAction::Fold).is_err());
+	}
+
+	#[test]
+	fn test_fold_to_raise() {
+		let mut state = start(&[100, 100, 100]);
+		state
+			.room
+			.seats
+			.iter_mut()
+			.flatten()
+			.for_each(|s| s.ready = true);
+
+		act(&mut state, 6);
+		fold(&mut state);
+		let events = state.apply(PlayerAction::Fold).unwrap();
+		assert!(state.game.is_over());
+		// no cards are dealt when everyone else folds
+		assert!(!records(&events).iter().any(|r| r.1 == Action::Deal));
+
+		let Some(Event::Over { results, pots }) = events.last() else {
+			panic!("game should be over");
+		};
+		assert_eq!(results[2].diff, 3);
+		assert_eq!(results[2].uncalled, 4);
+		assert!(results[2].winner);
+		assert_eq!(results[0].diff, -1);
+		assert_eq!(pots.len(), 1);
+		assert_eq!(pots[0].winners, vec![(3, 5)]);
+		assert_eq!(stack(&state, 2), 103);
+		assert_eq!(state.room.seats[2].as_ref().unwrap().guest.bankroll, 3);
+		assert!(state.room.seats.iter().flatten().all(|s| !s.ready));
+	}
+
+	#[test]
+	fn test_side_pots() {
+		let (mut state, _) = start_with(
+			&[50, 100, 100],
+			RoomSettings::default(),
+			deck(&["SA HA", "SK HK", "SQ HQ"], "C2 D7 C9 S3 H8"),
+		);
+
+		act(&mut state, 100);
+		act(&mut state, 49);
+		let events = state.apply(PlayerAction::Call).unwrap();
+		assert!(state.game.is_over());
+
+		let Some(Event::Over { results, pots }) = events.last() else {
+			panic!("game should be over");
+		};
+		assert_eq!(pots.len(), 2);
+		assert_eq!(pots[0].chips, 150);
+		assert_eq!(pots[0].eligible, vec![1, 2, 3]);
+		assert_eq!(pots[0].winners, vec![(1, 150)]);
+		assert_eq!(pots[1].chips, 100);
+		assert_eq!(pots[1].winners, vec![(2, 100)]);
+		let diffs: Vec<_> = results.iter().map(|r| r.diff).collect();
+		assert_eq!(diffs, vec![100, 0, -100]);
+		assert_eq!(
+			(stack(&state, 0), stack(&state, 1), stack(&state, 2)),
+			(150, 100, 0)
+		);
+	}
+
+	#[test]
+	fn test_split_pot() {
+		let (mut state, _) = start_with(
+			&[100, 100],
+			RoomSettings::default(),
+			deck(&["H2 H3", "D2 D3"], "SA SK SQ SJ ST"),
+		);
+
+		act(&mut state, 99);
+		let events = state.apply(PlayerAction::Call).unwrap();
+		assert!(state.game.is_over());
+
+		let Some(Event::Over { results, .. }) = events.last() else {
+			panic!("game should be over");
+		};
+		assert!(results.iter().all(|r| r.winner && r.diff == 0));
+		assert_eq!((stack(&state, 0), stack(&state, 1)), (100, 100));
+	}
+
+	/// Show and muck records of the events
+	fn reveals(events: &[Event]) -> Vec<(Option<usize>, Action)> {
+		records(events)
+			.into_iter()
+			.filter(|(_, action, _)| matches!(action, Action::Show | Action::Muck))
+			.map(|(position, action, _)| (position, action))
+			.collect()
+	}
+
+	/// Three players with pocket aces, seven-two and kings see the river
+	fn river() -> GameState {
+		let (mut state, _) = start_with(
+			&[100, 100, 100],
+			RoomSettings::default(),
+			deck(&["SA HA", "S7 H2", "SK HK"], "C3 D8 C9 S4 HJ"),
+		);
+		for chips in [2, 1, 0] {
+			act(&mut state, chips);
+		}
+		for _ in 0..6 {
+			act(&mut state, 0);
+		}
+		assert_eq!(state.game.round, Round::River);
+		state
+	}
+
+	#[test]
+	fn test_showdown_checked() {
+		let mut state = river();
+		act(&mut state, 0);
+		act(&mut state, 0);
+		let events = state.apply(PlayerAction::Check).unwrap();
+
+		// the first player after the button shows first, losing hands are mucked
+		assert_eq!(
+			reveals(&events),
+			vec![
+				(Some(0), Action::Show),
+				(Some(1), Action::Muck),
+				(Some(2), Action::Muck)
+			]
+		);
+		assert_eq!(state.shown, {
+			let mut shown = [false; Room::MAX_SEATS];
+			shown[0] = true;
+			shown
+		});
+	}
+
+	#[test]
+	fn test_showdown_aggressor() {
+		let mut state = river();
+		act(&mut state, 0);
+		act(&mut state, 10);
+		act(&mut state, 10);
+		let events = state.apply(PlayerAction::Call).unwrap();
+
+		// the bettor shows first, each better hand must be shown
+		assert_eq!(state.showdown_order(), vec![1, 2, 0]);
+		assert_eq!(
+			reveals(&events),
+			vec![
+				(Some(1), Action::Show),
+				(Some(2), Action::Show),
+				(Some(0), Action::Show)
+			]
+		);
+	}
+
+	#[test]
+	fn test_showdown_allin() {
+		let (mut state, _) = start_with(
+			&[100, 100],
+			RoomSettings::default(),
+			deck(&["SA HA", "S7 H2"], "C3 D8 C9 S4 HJ"),
+		);
+		act(&mut state, 99);
+		let events = state.apply(PlayerAction::Call).unwrap();
+
+		// every hand is shown when someone is all-in
+		assert_eq!(
+			reveals(&events),
+			vec![(Some(1), Action::Show), (Some(0), Action::Show)]
+		);
+	}
+
+	#[test]
+	fn test_allin_equity() {
+		let (mut state, _) = start_with(
+			&[100, 100],
+			RoomSettings::default(),
+			deck(&["SA HA", "S7 H2"], "C3 D8 C9 S4 HJ"),
+		);
+		act(&mut state, 99);
+		let events = state.apply(PlayerAction::Call).unwrap();
+
+		// the odds are published before the runout, then after each street
+		let equities: Vec<_> = events
+			.iter()
+			.filter_map(|e| match e {
+				Event::Equity(equity) => Some(equity),
+				_ => None,
+			})
+			.collect();
+		let rounds: Vec<_> = equities.iter().map(|e| e.round).collect();
+		assert_eq!(
+			rounds,
+			vec![Round::PreFlop, Round::Flop, Round::Turn, Round::River]
+		);
+		let flop = events
+			.iter()
+			.position(|e| matches!(e, Event::Record(r) if r.round == Round::Flop))
+			.unwrap();
+		assert!(matches!(&events[flop + 1], Event::Equity(e) if e.round == Round::Flop));
+
+		let preflop = &equities[0].players;
+		assert_eq!((preflop[0].position, preflop[0].guest_id), (0, 1));
+		assert!(preflop[0].equity.win > 80.0);
+		let total: f64 = preflop.iter().map(|p| p.equity.equity).sum();
+		assert!((total - 100.0).abs() < 1e-9);
+		assert_eq!(equities[3].players[0].equity.win, 100.0);
+
+		// no runout when the betting goes on
+		let mut state = start(&[100, 100]);
+		let events = state.apply(PlayerAction::Call).unwrap();
+		assert!(!events.iter().any(|e| matches!(e, Event::Equity(_))));
+	}
+
+	#[test]
+	fn test_allin_equity_by_hand() {
+		let (hands, board) = (["SA HA", "ST HJ"], "C3 D8 C9 S4 H7");
+		let (mut state, _) = start_with(&[100, 100], RoomSettings::default(), deck(&hands, board));
+		let hands: Vec<Vec<_>> = hands
+			.iter()
+			.map(|h| h.split(' ').map(Card::parse).collect())
+			.collect();
+		let board: Vec<_> = board.split(' ').map(Card::parse).collect();
+		while state.game.round != Round::Turn {
+			let legal = state.game.legal_actions(&state.room).unwrap();
+			let action = if legal.call.is_some() {
+				PlayerAction::Call
+			} else {
+				PlayerAction::Check
+			};
+			state.apply(action).unwrap();
+		}
+		state.apply(PlayerAction::AllIn).unwrap();
+		let events = state.apply(PlayerAction::Call).unwrap();
+		let turn = events
+			.iter()
+			.find_map(|e| match e {
+				Event::Equity(equity) if equity.round == Round::Turn => Some(equity),
+				_ => None,
+			})
+			.unwrap();
+
+		// rivers won by each hand as ranked by Hand
+		let mut wins = [0; 2];
+		let rivers: Vec<_> = Card::new_sorted()
+			.into_iter()
+			.filter(|c| !hands.iter().flatten().chain(&board[..4]).any(|d| d == c))
+			.collect();
+		for &river in &rivers {
+			let mut common = board[..4].to_vec();
+			common.push(river);
+			let best: Vec<_> = hands
+				.iter()
+				.map(|hand| Hand::calc_best_hand(&common, hand))
+				.collect();
+			if best[0] > best[1] {
+				wins[0] += 1;
+			} else if best[1] > best[0] {
+				wins[1] += 1;
+			}
+		}
+		for (p, player) in turn.players.iter().enumerate() {
+			let expected = f64::from(wins[p]) * 100.0 / rivers.len() as f64;
+			assert!((player.equity.win - expected).abs() < 1e-9);
+		}
+	}
+
+	#[test]
+	fn test_show() {
+		let mut state = start(&[100, 100, 100]);
+		assert!(state.show(1).is_err());
+
+		fold(&mut state);
+		fold(&mut state);
+		assert!(state.game.is_over());
+		// nothing is revealed without a showdown
+		assert!(state.shown.iter().all(|&shown| !shown));
+
+		let events = state.show(1).unwrap();
+		assert_eq!(records(&events), vec![(Some(0), Action::Show, 0)]);
+		assert!(state.shown[0]);
+		assert!(state.show(1).is_err());
+		assert!(state.show(4).is_err());
+		assert!(state.apply(PlayerAction::Show).is_err());
+	}
+}
@@ -0,0 +1,167 @@
+pub mod ohh;
+pub mod pokerstars;
+pub mod replay;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Action, Card, Game, GameResult, Guest, PotResult, Rank, Record, RoomSettings, Suit};
+
+/// A player dealt in a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPlayer {
+	pub position: usize,
+	pub guest: Guest,
+	/// Stack before antes and blinds
+	pub stack: usize,
+}
+
+/// Everything about a finished game needed to export its hand history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HandHistory {
+	pub game: Game,
+	pub settings: RoomSettings,
+	/// UTC time when the game started, like `2025-01-31 18:30:00`
+	pub started_at: String,
+	pub button: usize,
+	/// Players in the order of their positions
+	pub players: Vec<HistoryPlayer>,
+	/// Records in order, hole cards are only dealt to the hero
+	pub records: Vec<Record>,
+	pub results: Vec<GameResult>,
+	pub pots: Vec<PotResult>,
+	/// The guest whom the history is exported for
+	pub hero: Option<usize>,
+}
+
+impl HandHistory {
+	#[must_use]
+	pub fn player(&self, position: usize) -> Option<&HistoryPlayer> {
+		self.players.iter().find(|p| p.position == position)
+	}
+
+	/// Common cards dealt in the game
+	#[must_use]
+	pub fn board(&self) -> Vec<Card> {
+		self.records
+			.iter()
+			.filter(|r| r.action == Action::Deal && r.guest_id.is_none())
+			.flat_map(|r| r.cards.iter().copied())
+			.collect()
+	}
+
+	/// Chips won from the pots by the guest, uncalled bets are not included
+	#[must_use]
+	pub fn won(&self, guest_id: usize) -> usize {
+		self.pots
+			.iter()
+			.flat_map(|pot| &pot.winners)
+			.filter(|&&(id, _)| id == guest_id)
+			.map(|&(_, chips)| chips)
+			.sum()
+	}
+
+	/// Whether hands were compared at the end of the game
+	#[must_use]
+	pub fn has_showdown(&self) -> bool {
+		let folded = self
+			.records
+			.iter()
+			.filter(|r| r.action == Action::Fold)
+			.count();
+		self.players.len() - folded > 1
+	}
+}
+
+/// Card in the notation of hand histories, like `Ah` and `Td`
+fn card_code(card: Card) -> String {
+	let suit = match card.suit {
+		Suit::Spade => 's',
+		Suit::Heart => 'h',
+		Suit::Diamond => 'd',
+		Suit::Club => 'c',
+	};
+	format!("{}{suit}", card.rank)
+}
+
+/// Parse a card like `Ah`, None if not valid
+fn parse_card_code(code: &str) -> Option<Card> {
+	let mut chars = code.chars();
+	let (Some(rank), Some(suit), None) = (chars.next(), chars.next(), chars.next()) else {
+		return None;
+	};
+	if !"23456789TJQKA".contains(rank) || !"shdc".contains(suit) {
+		return None;
+	}
+
+	Some(Card {
+		rank: Rank::parse(rank),
+		suit: Suit::parse(suit.to_ascii_uppercase()),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		Room,
+		engine::{Event, GameState, PlayerAction},
+	};
+
+	/// Play the actions heads-up, Alice is on the big blind and Bob on the button
+	pub(super) fn play(deck: &str, actions: &[PlayerAction]) -> HandHistory {
+		play_with(RoomSettings::default(), deck, actions)
+	}
+
+	/// Play the actions heads-up in a room with the settings
+	pub(super) fn play_with(
+		settings: RoomSettings,
+		deck: &str,
+		actions: &[PlayerAction],
+	) -> HandHistory {
+		let mut room = Room::new(1);
+		room.settings = settings.clone();
+		room.insert(Guest::new(1, "Alice")).unwrap();
+		room.insert(Guest::new(2, "Bob")).unwrap();
+		let mut deck: Vec<_> = deck.split(' ').map(Card::parse).collect();
+		deck.reverse();
+
+		let players = (0..2)
+			.map(|position| HistoryPlayer {
+				position,
+				guest: room.get_guest(position).unwrap().clone(),
+				stack: room.settings.starting_stack,
+			})
+			.collect();
+		let (mut state, mut events) = GameState::start(room, 1, deck);
+		for &action in actions {
+			events.extend(state.apply(action).unwrap());
+		}
+
+		let mut records = Vec::new();
+		let (mut results, mut pots) = (Vec::new(), Vec::new());
+		for event in events {
+			match event {
+				Event::Record(record) => records.push(record),
+				Event::Round(_) | Event::Equity(_) => (),
+				Event::Over {
+					results: r,
+					pots: p,
+				} => (results, pots) = (r, p),
+			}
+		}
+		// only Alice's hole cards are seen
+		records.retain(|r| !r.is_hole_cards() || r.guest_id == Some(1));
+
+		HandHistory {
+			game: state.game,
+			settings,
+			started_at: "2025-01-31 18:30:00".to_string(),
+			button: state.room.button,
+			players,
+			records,
+			results,
+			pots,
+			hero: Some(1),
+		}
+	}
+}
@@ -0,0 +1,729 @@
+//! [Open Hand History](https://hh-specs.handhistory.org/) JSON format
+//!
+//! Amounts of actions are the chips put into the pot by the actions, like
+//! [`Record::chips`].
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	Action, Card, CardSet, Contribution, Game, GameResult, Guest, Pot, PotResult, Record, Room,
+	RoomSettings, Round,
+	error::{Result, bad_request_error},
+};
+
+use super::{HandHistory, HistoryPlayer, card_code, parse_card_code};
+
+/// Version of the spec written by this module
+pub const SPEC_VERSION: &str = "1.4.6";
+
+/// Root object of an OHH file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ohh {
+	pub ohh: OhhHand,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhHand {
+	pub spec_version: String,
+	#[serde(default)]
+	pub site_name: String,
+	#[serde(default)]
+	pub network_name: String,
+	#[serde(default)]
+	pub internal_version: String,
+	#[serde(default)]
+	pub tournament: bool,
+	pub game_number: String,
+	/// ISO 8601 time like `2025-01-31T18:30:00Z`
+	pub start_date_utc: String,
+	#[serde(default)]
+	pub table_name: String,
+	pub table_size: usize,
+	pub game_type: String,
+	pub bet_limit: OhhBetLimit,
+	pub dealer_seat: usize,
+	pub small_blind_amount: f64,
+	pub big_blind_amount: f64,
+	#[serde(default)]
+	pub ante_amount: f64,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub hero_player_id: Option<usize>,
+	pub players: Vec<OhhPlayer>,
+	pub rounds: Vec<OhhRound>,
+	pub pots: Vec<OhhPot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhBetLimit {
+	pub bet_type: String,
+	#[serde(default)]
+	pub bet_cap: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhPlayer {
+	pub id: usize,
+	pub seat: usize,
+	pub name: String,
+	pub starting_stack: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhRound {
+	pub id: usize,
+	#[serde(with = "street")]
+	pub street: Round,
+	/// Common cards dealt in this round
+	#[serde(default, with = "cards", skip_serializing_if = "Vec::is_empty")]
+	pub cards: Vec<Card>,
+	pub actions: Vec<OhhAction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhAction {
+	pub action_number: usize,
+	pub player_id: usize,
+	pub action: OhhActionKind,
+	#[serde(default)]
+	pub amount: f64,
+	#[serde(default)]
+	pub is_allin: bool,
+	/// Dealt or shown hole cards
+	#[serde(default, with = "cards", skip_serializing_if = "Vec::is_empty")]
+	pub cards: Vec<Card>,
+}
+
+/// Actions of the spec which are supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OhhActionKind {
+	#[serde(rename = "Dealt Cards")]
+	DealtCards,
+	#[serde(rename = "Post Ante")]
+	PostAnte,
+	#[serde(rename = "Post SB")]
+	PostSb,
+	#[serde(rename = "Post BB")]
+	PostBb,
+	Fold,
+	Check,
+	Bet,
+	Raise,
+	Call,
+	#[serde(rename = "Shows Cards")]
+	ShowsCards,
+	#[serde(rename = "Mucks Cards")]
+	MucksCards,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhPot {
+	pub number: usize,
+	pub amount: f64,
+	#[serde(default)]
+	pub rake: f64,
+	pub player_wins: Vec<OhhWin>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OhhWin {
+	pub player_id: usize,
+	pub win_amount: f64,
+}
+
+impl HandHistory {
+	/// Convert the history to OHH, hole cards are only dealt to the hero
+	#[must_use]
+	pub fn to_ohh(&self) -> Ohh {
+		let mut rounds: Vec<OhhRound> = Vec::new();
+		let mut bets: HashMap<usize, usize> = HashMap::new();
+		let mut max_bet = 0;
+
+		for record in &self.records {
+			if record.action == Action::Deal && !record.is_hole_cards() {
+				bets.clear();
+				max_bet = 0;
+				rounds.push(OhhRound {
+					id: rounds.len(),
+					street: record.round,
+					cards: record.cards.clone(),
+					actions: Vec::new(),
+				});
+				continue;
+			}
+			if rounds.last().is_none_or(|r| r.street != record.round) {
+				rounds.push(OhhRound {
+					id: rounds.len(),
+					street: record.round,
+					cards: Vec::new(),
+					actions: Vec::new(),
+				});
+			}
+
+			let action = match record.action {
+				Action::Deal => OhhActionKind::DealtCards,
+				Action::Ante => OhhActionKind::PostAnte,
+				Action::SmallBlind => OhhActionKind::PostSb,
+				Action::BigBlind => OhhActionKind::PostBb,
+				Action::Check => OhhActionKind::Check,
+				Action::Fold => OhhActionKind::Fold,
+				Action::Show => OhhActionKind::ShowsCards,
+				Action::Muck => OhhActionKind::MucksCards,
+				Action::Call | Action::Bet | Action::Raise | Action::AllIn => {
+					let bet = bets.entry(record.position.unwrap()).or_default();
+					*bet += record.chips;
+					if *bet <= max_bet {
+						OhhActionKind::Call
+					} else if max_bet == 0 {
+						OhhActionKind::Bet
+					} else {
+						OhhActionKind::Raise
+					}
+				}
+			};
+			if matches!(record.action, Action::SmallBlind | Action::BigBlind) {
+				let bet = bets.entry(record.position.unwrap()).or_default();
+				*bet += record.chips;
+			}
+			max_bet = bets.values().copied().max().unwrap_or(0);
+
+			let action_number = rounds.iter().map(|r| r.actions.len()).sum::<usize>() + 1;
+			rounds.last_mut().unwrap().actions.push(OhhAction {
+				action_number,
+				player_id: record.guest_id.unwrap(),
+				action,
+				amount: record.chips as f64,
+				is_allin: record.action == Action::AllIn,
+				cards: record.cards.clone(),
+			});
+		}
+
+		let settings = &self.settings;
+		Ohh {
+			ohh: OhhHand {
+				spec_version: SPEC_VERSION.to_string(),
+				site_name: "Open Poker".to_string(),
+				network_name: "Open Poker".to_string(),
+				internal_version: env!("CARGO_PKG_VERSION").to_string(),
+				tournament: false,
+				game_number: self.game.id.to_string(),
+				start_date_utc: format!("{}Z", self.started_at.replace(' ', "T")),
+				table_name: format!("Room {}", self.game.room_id),
+				table_size: Room::MAX_SEATS,
+				game_type: "Holdem".to_string(),
+				bet_limit: OhhBetLimit {
+					bet_type: "NL".to_string(),
+					bet_cap: 0.0,
+				},
+				dealer_seat: self.button + 1,
+				small_blind_amount: settings.small_blind as f64,
+				big_blind_amount: settings.big_blind as f64,
+				ante_amount: settings.ante as f64,
+				hero_player_id: self.hero,
+				players: self
+					.players
+					.iter()
+					.map(|p| OhhPlayer {
+						id: p.guest.id,
+						seat: p.position + 1,
+						name: p.guest.name.clone(),
+						starting_stack: p.stack as f64,
+					})
+					.collect(),
+				rounds,
+				pots: self
+					.pots
+					.iter()
+					.map(|pot| OhhPot {
+						number: pot.number,
+						amount: pot.chips as f64,
+						rake: 0.0,
+						player_wins: pot
+							.winners
+							.iter()
+							.map(|&(player_id, chips)| OhhWin {
+								player_id,
+								win_amount: chips as f64,
+							})
+							.collect(),
+					})
+					.collect(),
+			},
+		}
+	}
+
+	/// Build a history from OHH
+	///
+	/// Player IDs of the OHH are used as guest IDs, and the game ID is 0.
+	/// Fractional amounts are converted to cents. All dealt and shown hole
+	/// cards are kept.
+	///
+	/// # Errors
+	///
+	/// Return bad request error if the hand is not supported or not valid
+	pub fn from_ohh(ohh: Ohh) -> Result<Self> {
+		let hand = ohh.ohh;
+		if hand.game_type != "Holdem" {
+			return Err(bad_request_error(format!(
+				"unsupported game type: {}",
+				hand.game_type
+			)));
+		}
+
+		let amounts = [
+			hand.small_blind_amount,
+			hand.big_blind_amount,
+			hand.ante_amount,
+		]
+		.into_iter()
+		.chain(hand.players.iter().map(|p| p.starting_stack))
+		.chain(
+			hand.rounds
+				.iter()
+				.flat_map(|r| r.actions.iter().map(|a| a.amount)),
+		)
+		.chain(hand.pots.iter().map(|p| p.amount))
+		.chain(
+			hand.pots
+				.iter()
+				.flat_map(|p| p.player_wins.iter().map(|w| w.win_amount)),
+		);
+		let scale = if amounts.clone().all(|a| a.fract() == 0.0) {
+			1.0
+		} else {
+			100.0
+		};
+		let chips = |amount: f64| {
+			let chips = amount * scale;
+			if chips < 0.0 || (chips - chips.round()).abs() > 1e-6 {
+				return Err(bad_request_error(format!("invalid amount: {amount}")));
+			}
+			Ok(chips.round() as usize)
+		};
+
+		let mut players = Vec::new();
+		let mut positions = HashMap::new();
+		for player in &hand.players {
+			if !(1..=Room::MAX_SEATS).contains(&player.seat)
+				|| players
+					.iter()
+					.any(|p: &HistoryPlayer| p.position == player.seat - 1)
+			{
+				return Err(bad_request_error(format!("invalid seat: {}", player.seat)));
+			}
+			if positions.insert(player.id, player.seat - 1).is_some() {
+				return Err(bad_request_error(format!(
+					"duplicate player: {}",
+					player.id
+				)));
+			}
+			players.push(HistoryPlayer {
+				position: player.seat - 1,
+				guest: Guest::new(player.id, &player.name),
+				stack: chips(player.starting_stack)?,
+			});
+		}
+		players.sort_by_key(|p| p.position);
+		if players.len() < 2 {
+			return Err(bad_request_error("there should be at least two players"));
+		}
+		if !(1..=Room::MAX_SEATS).contains(&hand.dealer_seat) {
+			return Err(bad_request_error(format!(
+				"invalid dealer seat: {}",
+				hand.dealer_seat
+			)));
+		}
+
+		let mut records = Vec::new();
+		for round in &hand.rounds {
+			if !round.cards.is_empty() {
+				records.push(Record {
+					game_id: 0,
+					seq: records.len(),
+					round: round.street,
+					position: None,
+					guest_id: None,
+					action: Action::Deal,
+					chips: 0,
+					cards: round.cards.clone(),
+				});
+			}
+			for action in &round.actions {
+				let &position = positions.get(&action.player_id).ok_or_else(|| {
+					bad_request_error(format!("unknown player: {}", action.player_id))
+				})?;
+				let kind = match action.action {
+					OhhActionKind::DealtCards => Action::Deal,
+					OhhActionKind::PostAnte => Action::Ante,
+					OhhActionKind::PostSb => Action::SmallBlind,
+					OhhActionKind::PostBb => Action::BigBlind,
+					OhhActionKind::Fold => Action::Fold,
+					OhhActionKind::Check => Action::Check,
+					OhhActionKind::ShowsCards => Action::Show,
+					OhhActionKind::MucksCards => Action::Muck,
+					_ if action.is_allin => Action::AllIn,
+					OhhActionKind::Bet => Action::Bet,
+					OhhActionKind::Raise => Action::Raise,
+					OhhActionKind::Call => Action::Call,
+				};
+				if matches!(kind, Action::Deal | Action::Show) && action.cards.len() > 2 {
+					return Err(bad_request_error("hole cards should be two cards"));
+				}
+				records.push(Record {
+					game_id: 0,
+					seq: records.len(),
+					round: round.street,
+					position: Some(position),
+					guest_id: Some(action.player_id),
+					action: kind,
+					chips: chips(action.amount)?,
+					cards: action.cards.clone(),
+				});
+			}
+		}
+
+		// a player may show the cards dealt, but no card is held twice
+		let mut used = CardSet::EMPTY;
+		for player in &players {
+			let cards: CardSet = records
+				.iter()
+				.filter(|r| r.position == Some(player.position))
+				.flat_map(|r| r.cards.iter().copied())
+				.collect();
+			if cards.len() > 2 {
+				return Err(bad_request_error("hole cards should be two cards"));
+			}
+			if let Some(card) = (used & cards).iter().next() {
+				return Err(bad_request_error(format!("card used twice: {card}")));
+			}
+			used |= cards;
+		}
+		for &card in records
+			.iter()
+			.filter(|r| r.position.is_none())
+			.flat_map(|r| &r.cards)
+		{
+			if !used.insert(card) {
+				return Err(bad_request_error(format!("card used twice: {card}")));
+			}
+		}
+
+		let contributions: Vec<_> = players
+			.iter()
+			.map(|p| {
+				let records = records.iter().filter(|r| r.position == Some(p.position));
+				Contribution::new(
+					p.position,
+					records.clone().map(|r| r.chips).sum(),
+					records.clone().any(|r| r.action == Action::Fold),
+				)
+			})
+			.collect();
+		for (player, contribution) in players.iter().zip(&contributions) {
+			if contribution.chips > player.stack {
+				return Err(bad_request_error(format!(
+					"player {} puts in more than the starting stack",
+					player.guest.id
+				)));
+			}
+		}
+		let pot: usize = contributions.iter().map(|c| c.chips).sum();
+		let (_, uncalled) = Pot::build(&contributions);
+
+		let mut pots = Vec::new();
+		for pot in &hand.pots {
+			let mut winners = Vec::new();
+			for win in &pot.player_wins {
+				if !positions.contains_key(&win.player_id) {
+					return Err(bad_request_error(format!(
+						"unknown player: {}",
+						win.player_id
+					)));
+				}
+				winners.push((win.player_id, chips(win.win_amount)?));
+			}
+			pots.push(PotResult {
+				game_id: 0,
+				number: pot.number,
+				chips: chips(pot.amount)?,
+				// only winners are known
+				eligible: winners.iter().map(|&(id, _)| id).collect(),
+				winners,
+			});
+		}
+
+		let results = players
+			.iter()
+			.zip(&contributions)
+			.map(|(player, contribution)| {
+				let id = player.guest.id;
+				let won: usize = pots
+					.iter()
+					.flat_map(|pot| &pot.winners)
+					.filter(|&&(winner, _)| winner == id)
+					.map(|&(_, chips)| chips)
+					.sum();
+				let returned = uncalled
+					.filter(|u| u.position == player.position)
+					.map_or(0, |u| u.chips);
+				let diff = (won + returned) as isize - contribution.chips as isize;
+				GameResult::new(0, id, diff, won > 0, returned)
+			})
+			.collect();
+
+		let big_blind = chips(hand.big_blind_amount)?;
+		let settings = RoomSettings {
+			small_blind: chips(hand.small_blind_amount)?,
+			big_blind,
+			ante: chips(hand.ante_amount)?,
+			starting_stack: players
+				.iter()
+				.map(|p| p.stack)
+				.max()
+				.unwrap()
+				.max(big_blind)
+				.max(RoomSettings::default().min_stack),
+			..RoomSettings::default()
+		};
+		settings.validate()?;
+
+		let started_at = hand
+			.start_date_utc
+			.get(..19)
+			.ok_or(bad_request_error("invalid start date"))?
+			.replace('T', " ");
+
+		Ok(Self {
+			game: Game {
+				id: 0,
+				room_id: 0,
+				round: Round::Over,
+				pot,
+				position: 0,
+				min_raise: 0,
+				acted: 0,
+				aggressor: None,
+			},
+			settings,
+			started_at,
+			button: hand.dealer_seat - 1,
+			players,
+			records,
+			results,
+			pots,
+			hero: hand.hero_player_id,
+		})
+	}
+}
+
+/// Serde of [`Round`] as OHH streets
+mod street {
+	use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+	use crate::Round;
+
+	pub fn serialize<S: Serializer>(round: &Round, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(match round {
+			Round::PreFlop => "Preflop",
+			Round::Flop => "Flop",
+			Round::Turn => "Turn",
+			Round::River => "River",
+			Round::Over => "Showdown",
+		})
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Round, D::Error> {
+		match String::deserialize(deserializer)?.as_str() {
+			"Preflop" => Ok(Round::PreFlop),
+			"Flop" => Ok(Round::Flop),
+			"Turn" => Ok(Round::Turn),
+			"River" => Ok(Round::River),
+			"Showdown" => Ok(Round::Over),
+			street => Err(D::Error::custom(format!("unknown street: {street}"))),
+		}
+	}
+}
+
+/// Serde of [`Card`]s as OHH cards like `Ah`
+mod cards {
+	use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+	use crate::Card;
+
+	use super::{card_code, parse_card_code};
+
+	pub fn serialize<S: Serializer>(cards: &[Card], serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_seq(cards.iter().map(|&c| card_code(c)))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Card>, D::Error> {
+		Vec::<String>::deserialize(deserializer)?
+			.iter()
+			.map(|code| {
+				parse_card_code(code)
+					.ok_or_else(|| D::Error::custom(format!("invalid card: {code}")))
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::{json, to_value};
+
+	use super::*;
+	use crate::{engine::PlayerAction, history::tests::play};
+
+	#[test]
+	fn test_to_ohh() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		let ohh = to_value(history.to_ohh()).unwrap();
+
+		assert_eq!(ohh["ohh"]["dealer_seat"], 2);
+		assert_eq!(ohh["ohh"]["start_date_utc"], "2025-01-31T18:30:00Z");
+		assert_eq!(
+			ohh["ohh"]["rounds"][0]["actions"][2],
+			json!({
+				"action_number": 3,
+				"player_id": 1,
+				"action": "Dealt Cards",
+				"amount": 0.0,
+				"is_allin": false,
+				"cards": ["As", "Ah"],
+			})
+		);
+		assert_eq!(ohh["ohh"]["rounds"][0]["actions"][3]["action"], "Raise");
+		assert_eq!(
+			ohh["ohh"]["pots"],
+			json!([{
+				"number": 0,
+				"amount": 4.0,
+				"rake": 0.0,
+				"player_wins": [{"player_id": 2, "win_amount": 4.0}],
+			}])
+		);
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[
+				PlayerAction::Call,
+				PlayerAction::Check,
+				PlayerAction::Bet(10),
+				PlayerAction::Call,
+				PlayerAction::Check,
+				PlayerAction::Check,
+				PlayerAction::Check,
+				PlayerAction::AllIn,
+				PlayerAction::Call,
+			],
+		);
+		let json = serde_json::to_string(&history.to_ohh()).unwrap();
+		let imported = HandHistory::from_ohh(serde_json::from_str(&json).unwrap()).unwrap();
+
+		assert_eq!(imported.button, history.button);
+		assert_eq!(imported.game.pot, history.game.pot);
+		assert_eq!(imported.board(), history.board());
+		assert_eq!(
+			to_value(&imported.players).unwrap(),
+			to_value(&history.players).unwrap()
+		);
+		for (imported, record) in imported.records.iter().zip(&history.records) {
+			assert_eq!(
+				(
+					imported.round,
+					imported.action,
+					imported.chips,
+					&imported.cards
+				),
+				(record.round, record.action, record.chips, &record.cards)
+			);
+			assert_eq!(imported.guest_id, record.guest_id);
+		}
+		assert_eq!(imported.records.len(), history.records.len());
+		for (imported, result) in imported.results.iter().zip(&history.results) {
+			assert_eq!(
+				(imported.guest_id, imported.diff, imported.winner),
+				(result.guest_id, result.diff, result.winner)
+			);
+		}
+		assert_eq!(imported.won(1), history.won(1));
+		assert_eq!(imported.won(2), history.won(2));
+	}
+
+	#[test]
+	fn test_invalid() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		let mut ohh = history.to_ohh();
+		ohh.ohh.game_type = "Omaha".to_string();
+		assert!(HandHistory::from_ohh(ohh).is_err());
+
+		let mut ohh = history.to_ohh();
+		ohh.ohh.players[1].seat = 1;
+		assert!(HandHistory::from_ohh(ohh).is_err());
+
+		let mut json = to_value(history.to_ohh()).unwrap();
+		json["ohh"]["rounds"][0]["actions"][2]["cards"] = json!(["Ax", "Ah"]);
+		assert!(serde_json::from_value::<Ohh>(json).is_err());
+	}
+
+	#[test]
+	fn test_duplicate_player() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		let mut ohh = history.to_ohh();
+		ohh.ohh.players[1].id = ohh.ohh.players[0].id;
+		let err = HandHistory::from_ohh(ohh).unwrap_err();
+		assert!(err.to_string().contains("duplicate player"), "{err}");
+	}
+
+	#[test]
+	fn test_duplicate_cards() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		// in two hands
+		let mut json = to_value(history.to_ohh()).unwrap();
+		json["ohh"]["rounds"][0]["actions"][3]["cards"] = json!(["As", "Kh"]);
+		let mut ohh: Ohh = serde_json::from_value(json).unwrap();
+		ohh.ohh.rounds[0].actions[3].action = OhhActionKind::DealtCards;
+		ohh.ohh.rounds[0].actions[3].amount = 0.0;
+		let err = HandHistory::from_ohh(ohh).unwrap_err();
+		assert!(err.to_string().contains("card used twice"), "{err}");
+
+		// in a hand and on the board
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::Call, PlayerAction::Check],
+		);
+		let mut json = to_value(history.to_ohh()).unwrap();
+		json["ohh"]["rounds"][1]["cards"] = json!(["As", "7d", "9c"]);
+		let ohh: Ohh = serde_json::from_value(json).unwrap();
+		let err = HandHistory::from_ohh(ohh).unwrap_err();
+		assert!(err.to_string().contains("card used twice"), "{err}");
+	}
+
+	#[test]
+	fn test_over_stack() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		let mut ohh = history.to_ohh();
+		ohh.ohh.players[1].starting_stack = 4.0;
+		let err = HandHistory::from_ohh(ohh).unwrap_err();
+		assert!(err.to_string().contains("starting stack"), "{err}");
+	}
+}
@@ -0,0 +1,451 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{Action, Card, Hand, Kind, Rank, Room, Round};
+
+use super::{HandHistory, card_code};
+
+impl HandHistory {
+	/// Render the history in PokerStars hand history format
+	///
+	/// Chips are written without a currency, like play money games. Hole cards
+	/// are only dealt to the hero, other hands are seen at showdown.
+	#[must_use]
+	pub fn to_pokerstars(&self) -> String {
+		let mut out = String::new();
+		let game = &self.game;
+		let settings = &self.settings;
+
+		writeln!(
+			out,
+			"PokerStars Hand #{}: Hold'em No Limit ({}/{}) - {} UTC",
+			game.id,
+			settings.small_blind,
+			settings.big_blind,
+			self.started_at.replace('-', "/")
+		)
+		.unwrap();
+		writeln!(
+			out,
+			"Table 'Room {}' {}-max Seat #{} is the button",
+			game.room_id,
+			Room::MAX_SEATS,
+			self.button + 1
+		)
+		.unwrap();
+		for player in &self.players {
+			writeln!(
+				out,
+				"Seat {}: {} ({} in chips)",
+				player.position + 1,
+				player.guest.name,
+				player.stack
+			)
+			.unwrap();
+		}
+
+		let name = |position: Option<usize>| {
+			position
+				.and_then(|p| self.player(p))
+				.map_or("?", |p| p.guest.name.as_str())
+		};
+		let posts = self
+			.records
+			.iter()
+			.take_while(|r| {
+				matches!(
+					r.action,
+					Action::Ante | Action::SmallBlind | Action::BigBlind
+				) || r.is_hole_cards()
+			})
+			.count();
+		let last_bet = self
+			.records
+			.iter()
+			.rposition(|r| !matches!(r.action, Action::Deal | Action::Show | Action::Muck));
+		let showdown_count = if self.has_showdown() {
+			self.players.len()
+				- self
+					.records
+					.iter()
+					.filter(|r| r.action == Action::Fold)
+					.count()
+		} else {
+			0
+		};
+
+		let mut bets: HashMap<usize, usize> = HashMap::new();
+		let mut max_bet = 0;
+		let mut board: Vec<Card> = Vec::new();
+		let mut reveals = 0;
+
+		for (i, record) in self.records.iter().enumerate() {
+			if i == posts {
+				out.push_str("*** HOLE CARDS ***\n");
+				for record in self.records[..posts].iter().filter(|r| r.is_hole_cards()) {
+					writeln!(
+						out,
+						"Dealt to {} {}",
+						name(record.position),
+						cards(&record.cards)
+					)
+					.unwrap();
+				}
+			}
+
+			let who = name(record.position);
+			match record.action {
+				Action::Deal if record.is_hole_cards() => (),
+				Action::Deal => {
+					bets.clear();
+					max_bet = 0;
+					let street = match record.round {
+						Round::Flop => "FLOP",
+						Round::Turn => "TURN",
+						_ => "RIVER",
+					};
+					if board.is_empty() {
+						writeln!(out, "*** {street} *** {}", cards(&record.cards)).unwrap();
+					} else {
+						writeln!(
+							out,
+							"*** {street} *** {} {}",
+							cards(&board),
+							cards(&record.cards)
+						)
+						.unwrap();
+					}
+					board.extend(&record.cards);
+				}
+				Action::Ante => {
+					writeln!(out, "{who}: posts the ante {}", record.chips).unwrap();
+				}
+				Action::SmallBlind | Action::BigBlind => {
+					let blind = if record.action == Action::SmallBlind {
+						"small"
+					} else {
+						"big"
+					};
+					let bet = bets.entry(record.position.unwrap()).or_default();
+					*bet += record.chips;
+					max_bet = max_bet.max(*bet);
+					writeln!(out, "{who}: posts {blind} blind {}", record.chips).unwrap();
+				}
+				Action::Check => writeln!(out, "{who}: checks").unwrap(),
+				Action::Fold => writeln!(out, "{who}: folds").unwrap(),
+				Action::Call | Action::Bet | Action::Raise | Action::AllIn => {
+					let bet = bets.entry(record.position.unwrap()).or_default();
+					*bet += record.chips;
+					let allin = if record.action == Action::AllIn {
+						" and is all-in"
+					} else {
+						""
+					};
+					if *bet <= max_bet {
+						writeln!(out, "{who}: calls {}{allin}", record.chips).unwrap();
+					} else if max_bet == 0 {
+						writeln!(out, "{who}: bets {}{allin}", record.chips).unwrap();
+					} else {
+						writeln!(out, "{who}: raises {} to {}{allin}", *bet - max_bet, *bet)
+							.unwrap();
+					}
+					max_bet = max_bet.max(*bet);
+				}
+				Action::Show | Action::Muck => {
+					if reveals == 0 && showdown_count > 0 {
+						out.push_str("*** SHOW DOWN ***\n");
+					}
+					reveals += 1;
+					if record.action == Action::Muck {
+						writeln!(out, "{who}: mucks hand").unwrap();
+					} else if board.len() == 5 {
+						let hand = Hand::calc_best_hand(&board, &record.cards);
+						writeln!(
+							out,
+							"{who}: shows {} ({})",
+							cards(&record.cards),
+							describe(&hand)
+						)
+						.unwrap();
+					} else {
+						writeln!(out, "{who}: shows {}", cards(&record.cards)).unwrap();
+					}
+					if reveals == showdown_count {
+						self.write_collected(&mut out);
+					}
+				}
+			}
+
+			if Some(i) == last_bet {
+				for result in self.results.iter().filter(|r| r.uncalled > 0) {
+					let name = self
+						.players
+						.iter()
+						.find(|p| p.guest.id == result.guest_id)
+						.map_or("?", |p| p.guest.name.as_str());
+					writeln!(out, "Uncalled bet ({}) returned to {name}", result.uncalled).unwrap();
+				}
+				if showdown_count == 0 {
+					self.write_collected(&mut out);
+				}
+			}
+		}
+
+		self.write_summary(&mut out, &board);
+
+		out
+	}
+
+	/// Who collected chips from which pot
+	fn write_collected(&self, out: &mut String) {
+		for pot in self.pots.iter().rev() {
+			let pot_name = match (self.pots.len(), pot.number) {
+				(1, _) => "pot".to_string(),
+				(_, 0) => "main pot".to_string(),
+				(2, _) => "side pot".to_string(),
+				(_, number) => format!("side pot-{number}"),
+			};
+			for &(guest_id, chips) in &pot.winners {
+				let player = self.players.iter().find(|p| p.guest.id == guest_id);
+				let name = player.map_or("?", |p| p.guest.name.as_str());
+				writeln!(out, "{name} collected {chips} from {pot_name}").unwrap();
+			}
+		}
+	}
+
+	fn write_summary(&self, out: &mut String, board: &[Card]) {
+		out.push_str("*** SUMMARY ***\n");
+
+		let total: usize = self.pots.iter().map(|pot| pot.chips).sum();
+		write!(out, "Total pot {total}").unwrap();
+		if self.pots.len() > 1 {
+			write!(out, " Main pot {}.", self.pots[0].chips).unwrap();
+			for pot in &self.pots[1..] {
+				if self.pots.len() == 2 {
+					write!(out, " Side pot {}.", pot.chips).unwrap();
+				} else {
+					write!(out, " Side pot-{} {}.", pot.number, pot.chips).unwrap();
+				}
+			}
+		}
+		out.push_str(" | Rake 0\n");
+		if !board.is_empty() {
+			writeln!(out, "Board {}", cards(board)).unwrap();
+		}
+
+		let showdown = self.has_showdown();
+		for player in &self.players {
+			let mut labels = String::new();
+			if player.position == self.button {
+				labels.push_str(" (button)");
+			}
+			for record in self
+				.records
+				.iter()
+				.filter(|r| r.position == Some(player.position))
+			{
+				match record.action {
+					Action::SmallBlind => labels.push_str(" (small blind)"),
+					Action::BigBlind => labels.push_str(" (big blind)"),
+					_ => (),
+				}
+			}
+
+			let id = player.guest.id;
+			let records: Vec<_> = self
+				.records
+				.iter()
+				.filter(|r| r.guest_id == Some(id))
+				.collect();
+			let won = self.won(id);
+			let status = if let Some(fold) = records.iter().find(|r| r.action == Action::Fold) {
+				match fold.round {
+					Round::PreFlop => "folded before Flop".to_string(),
+					round => format!("folded on the {}", street_name(round)),
+				}
+			} else if !showdown {
+				format!("collected ({won})")
+			} else {
+				match records
+					.iter()
+					.find(|r| matches!(r.action, Action::Show | Action::Muck))
+				{
+					Some(record) if record.action == Action::Show && board.len() == 5 => {
+						let hand = Hand::calc_best_hand(board, &record.cards);
+						if won > 0 {
+							format!(
+								"showed {} and won ({won}) with {}",
+								cards(&record.cards),
+								describe(&hand)
+							)
+						} else {
+							format!(
+								"showed {} and lost with {}",
+								cards(&record.cards),
+								describe(&hand)
+							)
+						}
+					}
+					_ => "mucked".to_string(),
+				}
+			};
+			writeln!(
+				out,
+				"Seat {}: {}{labels} {status}",
+				player.position + 1,
+				player.guest.name
+			)
+			.unwrap();
+		}
+	}
+}
+
+fn street_name(round: Round) -> &'static str {
+	match round {
+		Round::Flop => "Flop",
+		Round::Turn => "Turn",
+		_ => "River",
+	}
+}
+
+/// Cards like `[Ah Td]`
+fn cards(cards: &[Card]) -> String {
+	let cards: Vec<_> = cards.iter().map(|&c| card_code(c)).collect();
+	format!("[{}]", cards.join(" "))
+}
+
+fn rank_name(rank: Rank) -> &'static str {
+	match rank {
+		Rank::Two => "Deuce",
+		Rank::Three => "Three",
+		Rank::Four => "Four",
+		Rank::Five => "Five",
+		Rank::Six => "Six",
+		Rank::Seven => "Seven",
+		Rank::Eight => "Eight",
+		Rank::Nine => "Nine",
+		Rank::Ten => "Ten",
+		Rank::J => "Jack",
+		Rank::Q => "Queen",
+		Rank::K => "King",
+		Rank::A => "Ace",
+	}
+}
+
+fn plural(rank: Rank) -> String {
+	match rank {
+		Rank::Six => "Sixes".to_string(),
+		rank => format!("{}s", rank_name(rank)),
+	}
+}
+
+/// Describe the hand like `a pair of Aces`
+fn describe(hand: &Hand) -> String {
+	let ranks: Vec<_> = hand.cards.iter().map(|c| c.rank).collect();
+	match hand.kind {
+		Kind::HighCard => format!("high card {}", rank_name(ranks[0])),
+		Kind::Pair => format!("a pair of {}", plural(ranks[0])),
+		Kind::TwoPairs => format!("two pair, {} and {}", plural(ranks[0]), plural(ranks[2])),
+		Kind::ThreeOfAKind => format!("three of a kind, {}", plural(ranks[0])),
+		Kind::Straight => format!(
+			"a straight, {} to {}",
+			rank_name(ranks[4]),
+			rank_name(ranks[0])
+		),
+		Kind::Flush => format!("a flush, {} high", rank_name(ranks[0])),
+		Kind::FullHouse => format!(
+			"a full house, {} full of {}",
+			plural(ranks[0]),
+			plural(ranks[3])
+		),
+		Kind::FourOfAKind => format!("four of a kind, {}", plural(ranks[0])),
+		Kind::StraightFlush => format!(
+			"a straight flush, {} to {}",
+			rank_name(ranks[4]),
+			rank_name(ranks[0])
+		),
+		Kind::RoyalFlush => "a Royal Flush".to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{engine::PlayerAction, history::tests::play};
+
+	#[test]
+	fn test_uncalled_bet() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::RaiseTo(6), PlayerAction::Fold],
+		);
+		assert_eq!(
+			history.to_pokerstars(),
+			"PokerStars Hand #1: Hold'em No Limit (1/2) - 2025/01/31 18:30:00 UTC
+Table 'Room 1' 10-max Seat #2 is the button
+Seat 1: Alice (1000 in chips)
+Seat 2: Bob (1000 in chips)
+Bob: posts small blind 1
+Alice: posts big blind 2
+*** HOLE CARDS ***
+Dealt to Alice [As Ah]
+Bob: raises 4 to 6
+Alice: folds
+Uncalled bet (4) returned to Bob
+Bob collected 4 from pot
+*** SUMMARY ***
+Total pot 4 | Rake 0
+Seat 1: Alice (big blind) folded before Flop
+Seat 2: Bob (button) (small blind) collected (4)
+"
+		);
+	}
+
+	#[test]
+	fn test_showdown() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[
+				PlayerAction::Call,
+				PlayerAction::Check,
+				PlayerAction::Bet(10),
+				PlayerAction::Call,
+				PlayerAction::Check,
+				PlayerAction::Check,
+				PlayerAction::Check,
+				PlayerAction::AllIn,
+				PlayerAction::Call,
+			],
+		);
+		let text = history.to_pokerstars();
+		assert!(text.contains("*** FLOP *** [2c 7d 9c]\nAlice: bets 10\nBob: calls 10\n"));
+		assert!(text.contains("*** RIVER *** [2c 7d 9c 3s] [8h]\n"));
+		assert!(text.contains(
+			"Alice: checks\nBob: bets 988 and is all-in\nAlice: calls 988 and is all-in\n"
+		));
+		assert!(text.contains(
+			"*** SHOW DOWN ***
+Bob: shows [Ks Kh] (a pair of Kings)
+Alice: shows [As Ah] (a pair of Aces)
+Alice collected 2000 from pot
+*** SUMMARY ***
+Total pot 2000 | Rake 0
+Board [2c 7d 9c 3s 8h]
+Seat 1: Alice (big blind) showed [As Ah] and won (2000) with a pair of Aces
+Seat 2: Bob (button) (small blind) showed [Ks Kh] and lost with a pair of Kings
+"
+		));
+	}
+
+	#[test]
+	fn test_describe() {
+		let hand = |cards: &str| {
+			let cards: Vec<_> = cards.split(' ').map(Card::parse).collect();
+			describe(&Hand::new(&cards))
+		};
+		assert_eq!(hand("SA H2 C3 D4 S5"), "a straight, Ace to Five");
+		assert_eq!(hand("S6 H6 C6 D9 S9"), "a full house, Sixes full of Nines");
+		assert_eq!(hand("SK HK C2 D2 S5"), "two pair, Kings and Deuces");
+		assert_eq!(hand("SA SK SQ SJ ST"), "a Royal Flush");
+		assert_eq!(hand("SA HK C2 D4 S5"), "high card Ace");
+	}
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Action, Card, GameResult, Record, Round};
+
+use super::{HandHistory, HistoryPlayer};
+
+/// A seat in a snapshot of the replay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaySeat {
+	pub position: usize,
+	pub guest_id: usize,
+	pub stack: usize,
+	/// Chips put into the pot in the game
+	pub bet: usize,
+	/// Chips put into the pot in the current round
+	pub round_bet: usize,
+	pub fold: bool,
+	/// Hole cards if visible to the viewer, empty otherwise
+	pub cards: Vec<Card>,
+}
+
+/// The table right after a record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+	pub record: Record,
+	pub round: Round,
+	pub pot: usize,
+	/// Common cards dealt so far
+	pub board: Vec<Card>,
+	pub seats: Vec<ReplaySeat>,
+}
+
+/// A finished game replayed record by record
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Replay {
+	pub game_id: usize,
+	pub button: usize,
+	pub sb: Option<usize>,
+	pub bb: Option<usize>,
+	/// Players with their positions and starting stacks
+	pub players: Vec<HistoryPlayer>,
+	pub snapshots: Vec<Snapshot>,
+	pub results: Vec<GameResult>,
+}
+
+impl HandHistory {
+	/// Replay the records of the game, one snapshot per record
+	///
+	/// Hole cards are as visible as in the records of the history.
+	///
+	/// # Panics
+	///
+	/// Panics if a player puts in more than the stack, which the engine and
+	/// [`Ohh`](super::ohh::Ohh) imports never let happen
+	#[must_use]
+	pub fn replay(&self) -> Replay {
+		let mut seats: Vec<_> = self
+			.players
+			.iter()
+			.map(|p| ReplaySeat {
+				position: p.position,
+				guest_id: p.guest.id,
+				stack: p.stack,
+				bet: 0,
+				round_bet: 0,
+				fold: false,
+				cards: Vec::new(),
+			})
+			.collect();
+		let mut board = Vec::new();
+		let mut round = Round::PreFlop;
+
+		let mut snapshots = Vec::new();
+		for record in &self.records {
+			if record.round != round {
+				round = record.round;
+				for seat in &mut seats {
+					seat.round_bet = 0;
+				}
+			}
+			match record
+				.position
+				.and_then(|p| seats.iter_mut().find(|s| s.position == p))
+			{
+				Some(seat) => {
+					seat.stack -= record.chips;
+					seat.bet += record.chips;
+					// antes are dead chips, not bets of the round
+					if record.action != Action::Ante {
+						seat.round_bet += record.chips;
+					}
+					match record.action {
+						Action::Fold => seat.fold = true,
+						Action::Deal | Action::Show if !record.cards.is_empty() => {
+							seat.cards.clone_from(&record.cards);
+						}
+						_ => (),
+					}
+				}
+				None => board.extend(record.cards.iter().copied()),
+			}
+
+			snapshots.push(Snapshot {
+				record: record.clone(),
+				round,
+				pot: seats.iter().map(|s| s.bet).sum(),
+				board: board.clone(),
+				seats: seats.clone(),
+			});
+		}
+
+		let blind = |action| {
+			self.records
+				.iter()
+				.find(|r| r.action == action)
+				.and_then(|r| r.position)
+		};
+		Replay {
+			game_id: self.game.id,
+			button: self.button,
+			sb: blind(Action::SmallBlind),
+			bb: blind(Action::BigBlind),
+			players: self.players.clone(),
+			snapshots,
+			results: self.results.clone(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		Action, RoomSettings,
+		engine::PlayerAction,
+		history::tests::{play, play_with},
+	};
+
+	#[test]
+	fn test_replay() {
+		let history = play(
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[
+				PlayerAction::Call,
+				PlayerAction::Check,
+				PlayerAction::Bet(10),
+				PlayerAction::Fold,
+			],
+		);
+		let replay = history.replay();
+
+		assert_eq!((replay.button, replay.sb, replay.bb), (1, Some(1), Some(0)));
+		assert_eq!(replay.snapshots.len(), history.records.len());
+
+		// Alice's hole cards are dealt to her only
+		let dealt = &replay.snapshots[2];
+		assert_eq!(dealt.seats[0].cards, history.records[2].cards);
+		assert!(dealt.seats[1].cards.is_empty());
+
+		let flop = replay
+			.snapshots
+			.iter()
+			.find(|s| s.board.len() == 3)
+			.unwrap();
+		assert_eq!(flop.pot, 4);
+		assert!(flop.seats.iter().all(|s| s.round_bet == 0));
+
+		let last = replay.snapshots.last().unwrap();
+		assert_eq!(last.pot, 14);
+		assert_eq!(last.seats[0].round_bet, 10);
+		assert_eq!(last.seats[0].stack, 988);
+		assert!(last.seats[1].fold);
+	}
+
+	#[test]
+	fn test_replay_ante() {
+		let settings = RoomSettings {
+			ante: 1,
+			..RoomSettings::default()
+		};
+		let history = play_with(
+			settings,
+			"SA HA SK HK C2 D7 C9 S3 H8",
+			&[PlayerAction::Call],
+		);
+		let replay = history.replay();
+
+		let antes = replay
+			.snapshots
+			.iter()
+			.rfind(|s| s.record.action == Action::Ante)
+			.unwrap();
+		assert_eq!(antes.pot, 2);
+		assert!(antes.seats.iter().all(|s| s.round_bet == 0));
+		assert!(antes.seats.iter().all(|s| s.stack == 999));
+
+		// the call completes the big blind, not the blind and the ante
+		let last = replay.snapshots.last().unwrap();
+		assert_eq!(last.pot, 6);
+		assert!(last.seats.iter().all(|s| s.round_bet == 2 && s.bet == 3));
+	}
+}
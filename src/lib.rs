@@ -1,7 +1,9 @@
 pub mod api;
 pub mod client;
 pub mod db;
+pub mod engine;
 pub mod error;
+pub mod history;
 pub mod model;
 pub mod utils;
 
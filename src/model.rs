@@ -1,15 +1,24 @@
 pub mod card;
+pub mod equity;
+pub mod eval;
 pub mod game;
 pub mod guest;
 pub mod hand;
+pub mod legal;
+pub mod pot;
+pub mod range;
 pub mod record;
 pub mod room;
 pub mod seat;
+pub mod settings;
 
 pub use card::*;
 pub use game::*;
 pub use guest::*;
 pub use hand::*;
+pub use legal::*;
+pub use pot::*;
 pub use record::*;
 pub use room::*;
 pub use seat::*;
+pub use settings::*;
@@ -1,9 +1,15 @@
-use std::fmt::Display;
+use std::{
+	fmt::Display,
+	ops::{BitAnd, BitOr, BitOrAssign, Not, Sub, SubAssign},
+	str::FromStr,
+};
 
-use rand::seq::SliceRandom;
+use rand::{Rng, seq::SliceRandom};
 use rusqlite::{ToSql, types::FromSql};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, Result, bad_request_error};
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Suit {
 	Spade,
@@ -262,6 +268,23 @@ pub enum ModernCard {
 
 pub type Deck = Vec<Card>;
 
+const RANKS: [Rank; 13] = [
+	Rank::Two,
+	Rank::Three,
+	Rank::Four,
+	Rank::Five,
+	Rank::Six,
+	Rank::Seven,
+	Rank::Eight,
+	Rank::Nine,
+	Rank::Ten,
+	Rank::J,
+	Rank::Q,
+	Rank::K,
+	Rank::A,
+];
+const SUITS: [Suit; 4] = [Suit::Spade, Suit::Heart, Suit::Diamond, Suit::Club];
+
 impl Card {
 	/// Create a new sorted deck
 	#[must_use]
@@ -298,6 +321,26 @@ impl Card {
 			rank: Rank::parse(card.chars().nth(1).unwrap()),
 		}
 	}
+
+	/// Index of the card from 0 to 51, 13 ranks of spades first, then hearts,
+	/// diamonds and clubs
+	#[must_use]
+	pub fn index(&self) -> usize {
+		self.suit as usize * RANKS.len() + self.rank as usize
+	}
+
+	/// Card of the index
+	///
+	/// # Panics
+	///
+	/// Will panic if `index` is not less than 52
+	#[must_use]
+	pub fn from_index(index: usize) -> Self {
+		Self {
+			rank: RANKS[index % RANKS.len()],
+			suit: SUITS[index / RANKS.len()],
+		}
+	}
 }
 
 impl Display for Card {
@@ -306,6 +349,26 @@ impl Display for Card {
 	}
 }
 
+impl FromStr for Card {
+	type Err = Error;
+
+	/// Parse database representation like `SA`
+	fn from_str(s: &str) -> Result<Self> {
+		let mut chars = s.chars();
+		match (chars.next(), chars.next(), chars.next()) {
+			(Some(suit @ ('S' | 'H' | 'D' | 'C')), Some(rank), None)
+				if "A23456789TJQK".contains(rank) =>
+			{
+				Ok(Self {
+					suit: Suit::parse(suit),
+					rank: Rank::parse(rank),
+				})
+			}
+			_ => Err(bad_request_error(format!("invalid card: {s}"))),
+		}
+	}
+}
+
 impl ToSql for Card {
 	fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
 		Ok(self.to_string().into())
@@ -320,7 +383,7 @@ impl FromSql for Card {
 
 impl PartialOrd for Card {
 	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-		Some(self.rank.cmp(&other.rank))
+		Some(self.cmp(other))
 	}
 }
 
@@ -330,6 +393,238 @@ impl Ord for Card {
 	}
 }
 
+/// Set of cards as a bitmask of their indexes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<Card>", into = "Vec<Card>")]
+pub struct CardSet(pub u64);
+
+impl CardSet {
+	pub const EMPTY: Self = Self(0);
+	/// All 52 cards
+	pub const FULL: Self = Self((1 << 52) - 1);
+
+	#[must_use]
+	pub fn len(self) -> usize {
+		self.0.count_ones() as usize
+	}
+
+	#[must_use]
+	pub fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	#[must_use]
+	pub fn contains(self, card: Card) -> bool {
+		self.0 & (1 << card.index()) != 0
+	}
+
+	/// Insert the card, return whether it was not in the set
+	pub fn insert(&mut self, card: Card) -> bool {
+		let inserted = !self.contains(card);
+		self.0 |= 1 << card.index();
+		inserted
+	}
+
+	/// Remove the card, return whether it was in the set
+	pub fn remove(&mut self, card: Card) -> bool {
+		let removed = self.contains(card);
+		self.0 &= !(1 << card.index());
+		removed
+	}
+
+	#[must_use]
+	pub fn is_disjoint(self, other: Self) -> bool {
+		self.0 & other.0 == 0
+	}
+
+	#[must_use]
+	pub fn is_subset(self, other: Self) -> bool {
+		self.0 & !other.0 == 0
+	}
+
+	/// Bitmask of the ranks of the suit, bit 0 is 2 and bit 12 is A
+	#[must_use]
+	pub fn suit_ranks(self, suit: Suit) -> u16 {
+		((self.0 >> (suit as usize * RANKS.len())) & 0x1fff) as u16
+	}
+
+	/// Iterate from the lowest index to the highest
+	#[must_use]
+	pub fn iter(self) -> CardSetIter {
+		CardSetIter(self.0)
+	}
+
+	/// Remove a random card from the set and return it, None if empty
+	pub fn deal(&mut self, rng: &mut impl Rng) -> Option<Card> {
+		if self.is_empty() {
+			return None;
+		}
+		// clear the lowest bits until the chosen one is the lowest
+		let mut bits = self.0;
+		for _ in 0..rng.random_range(0..self.len()) {
+			bits &= bits - 1;
+		}
+		let card = Card::from_index(bits.trailing_zeros() as usize);
+		self.remove(card);
+		Some(card)
+	}
+}
+
+/// Iterator of the cards of a [`CardSet`]
+pub struct CardSetIter(u64);
+
+impl Iterator for CardSetIter {
+	type Item = Card;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.0 == 0 {
+			return None;
+		}
+		let index = self.0.trailing_zeros() as usize;
+		self.0 &= self.0 - 1;
+		Some(Card::from_index(index))
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.0.count_ones() as usize;
+		(len, Some(len))
+	}
+}
+
+impl ExactSizeIterator for CardSetIter {}
+
+impl IntoIterator for CardSet {
+	type Item = Card;
+	type IntoIter = CardSetIter;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+impl FromIterator<Card> for CardSet {
+	fn from_iter<T: IntoIterator<Item = Card>>(iter: T) -> Self {
+		let mut set = Self::EMPTY;
+		set.extend(iter);
+		set
+	}
+}
+
+impl Extend<Card> for CardSet {
+	fn extend<T: IntoIterator<Item = Card>>(&mut self, iter: T) {
+		for card in iter {
+			self.insert(card);
+		}
+	}
+}
+
+impl From<Card> for CardSet {
+	fn from(card: Card) -> Self {
+		Self(1 << card.index())
+	}
+}
+
+impl From<&[Card]> for CardSet {
+	fn from(cards: &[Card]) -> Self {
+		cards.iter().copied().collect()
+	}
+}
+
+impl From<Vec<Card>> for CardSet {
+	fn from(cards: Vec<Card>) -> Self {
+		cards.into_iter().collect()
+	}
+}
+
+impl From<CardSet> for Deck {
+	fn from(set: CardSet) -> Self {
+		set.iter().collect()
+	}
+}
+
+impl BitOr for CardSet {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self {
+		Self(self.0 | rhs.0)
+	}
+}
+
+impl BitOrAssign for CardSet {
+	fn bitor_assign(&mut self, rhs: Self) {
+		self.0 |= rhs.0;
+	}
+}
+
+impl BitAnd for CardSet {
+	type Output = Self;
+
+	fn bitand(self, rhs: Self) -> Self {
+		Self(self.0 & rhs.0)
+	}
+}
+
+impl Sub for CardSet {
+	type Output = Self;
+
+	/// Cards in `self` but not in `rhs`
+	fn sub(self, rhs: Self) -> Self {
+		Self(self.0 & !rhs.0)
+	}
+}
+
+impl SubAssign for CardSet {
+	fn sub_assign(&mut self, rhs: Self) {
+		self.0 &= !rhs.0;
+	}
+}
+
+impl Not for CardSet {
+	type Output = Self;
+
+	/// Cards of the deck not in the set
+	fn not(self) -> Self {
+		Self(!self.0 & Self::FULL.0)
+	}
+}
+
+impl Display for CardSet {
+	/// Cards separated by spaces, like `SA HK`
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (i, card) in self.iter().enumerate() {
+			if i > 0 {
+				write!(f, " ")?;
+			}
+			write!(f, "{card}")?;
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for CardSet {
+	type Err = Error;
+
+	/// Parse cards separated by spaces or commas, or written together
+	fn from_str(s: &str) -> Result<Self> {
+		let cards: String = s
+			.chars()
+			.filter(|c| !c.is_whitespace() && *c != ',')
+			.collect();
+		if !cards.len().is_multiple_of(2) || !cards.is_ascii() {
+			return Err(bad_request_error(format!("invalid cards: {s}")));
+		}
+
+		let mut set = Self::EMPTY;
+		for i in (0..cards.len()).step_by(2) {
+			let card = cards[i..i + 2].parse()?;
+			if !set.insert(card) {
+				return Err(bad_request_error(format!("duplicate card: {card}")));
+			}
+		}
+		Ok(set)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -356,4 +651,76 @@ mod tests {
 		let d2 = Card::new_deck();
 		assert_ne!(d1, d2);
 	}
+
+	#[test]
+	fn test_index() {
+		for card in Card::new_sorted() {
+			assert!(card.index() < 52);
+			assert_eq!(Card::from_index(card.index()), card);
+		}
+		assert_eq!(Card::parse("S2").index(), 0);
+		assert_eq!(Card::parse("CA").index(), 51);
+	}
+
+	#[test]
+	fn test_parse() {
+		assert_eq!("HT".parse::<Card>().unwrap(), Card::parse("HT"));
+		assert!("HX".parse::<Card>().is_err());
+		assert!("H10".parse::<Card>().is_err());
+	}
+
+	#[test]
+	fn test_card_set() {
+		let mut set: CardSet = "SA HK".parse().unwrap();
+		assert_eq!(set.len(), 2);
+		assert!(set.contains(Card::parse("SA")));
+		assert!(!set.insert(Card::parse("SA")));
+		assert!(set.insert(Card::parse("D2")));
+		assert_eq!(set.to_string(), "SA HK D2");
+		assert_eq!("SAHK,D2".parse::<CardSet>().unwrap(), set);
+		assert!(set.remove(Card::parse("HK")));
+		assert!(!set.remove(Card::parse("HK")));
+
+		let other: CardSet = "SA CA".parse().unwrap();
+		assert_eq!((set | other).len(), 3);
+		assert_eq!(set & other, CardSet::from(Card::parse("SA")));
+		assert_eq!(set - other, CardSet::from(Card::parse("D2")));
+		assert_eq!((!set).len(), 50);
+		assert!(set.is_disjoint(!set));
+		assert!((set & other).is_subset(set));
+
+		assert!("SA SA".parse::<CardSet>().is_err());
+		assert!("SA H".parse::<CardSet>().is_err());
+	}
+
+	#[test]
+	fn test_card_set_conversions() {
+		let deck = Card::new_deck();
+		let set = CardSet::from(&deck[..]);
+		assert_eq!(set, CardSet::FULL);
+		assert_eq!(
+			Deck::from(set),
+			(0..52).map(Card::from_index).collect::<Deck>()
+		);
+
+		let set: CardSet = "SA HK D2".parse().unwrap();
+		let json = serde_json::to_string(&set).unwrap();
+		assert_eq!(serde_json::from_str::<CardSet>(&json).unwrap(), set);
+		assert_eq!(
+			serde_json::from_str::<Vec<Card>>(&json).unwrap(),
+			Deck::from(set)
+		);
+	}
+
+	#[test]
+	fn test_deal() {
+		let mut rng = rand::rng();
+		let mut set = CardSet::FULL;
+		let mut dealt = CardSet::EMPTY;
+		while let Some(card) = set.deal(&mut rng) {
+			assert!(dealt.insert(card));
+			assert!(!set.contains(card));
+		}
+		assert_eq!(dealt, CardSet::FULL);
+	}
 }
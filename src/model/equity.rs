@@ -0,0 +1,536 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, bad_request_error};
+
+use super::{
+	Card, CardSet, Hand, Round,
+	range::{Combo, Range},
+};
+
+/// Chances of a player to win the pot, in percentages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Equity {
+	/// Boards won alone
+	pub win: f64,
+	/// Boards tied with other players
+	pub tie: f64,
+	/// Share of the pot expected, ties are split evenly
+	pub equity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EquityResult {
+	/// Equities in the order of the hands
+	pub players: Vec<Equity>,
+	/// Count of the boards evaluated
+	pub boards: usize,
+	/// Whether every board was evaluated, otherwise they were sampled
+	pub exhaustive: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EquityOptions {
+	/// Enumerate every board if there are at most this many
+	pub max_exhaustive: usize,
+	/// Boards sampled by Monte Carlo otherwise
+	pub samples: usize,
+	/// Seed of the samples, the same seed gives the same result
+	pub seed: u64,
+}
+
+impl Default for EquityOptions {
+	fn default() -> Self {
+		Self {
+			max_exhaustive: 100_000,
+			samples: 100_000,
+			seed: 0,
+		}
+	}
+}
+
+/// Chances of a player to win the pot during an all-in runout
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerEquity {
+	pub guest_id: usize,
+	pub position: usize,
+	#[serde(flatten)]
+	pub equity: Equity,
+}
+
+/// Equities of the players left once the cards of a round are dealt
+///
+/// When every player left is all-in, the board runs out street by street and
+/// the equities are published after each street, starting with the round in
+/// which the betting closed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreetEquity {
+	pub game_id: usize,
+	pub round: Round,
+	pub players: Vec<PlayerEquity>,
+}
+
+/// Calculate the equities of the hole cards against each other
+///
+/// The board is completed from the cards of [`Card::new_sorted`] not in any
+/// hand, on the board or dead. Hands are ranked by [`Hand::calc_best_hand`].
+///
+/// # Errors
+///
+/// Return bad request error if a hand is not two cards, the board is more than
+/// five cards, or a card is used twice
+pub fn calc_equity(
+	hands: &[Vec<Card>],
+	board: &[Card],
+	dead: &[Card],
+	options: &EquityOptions,
+) -> Result<EquityResult> {
+	if hands.iter().any(|hand| hand.len() != 2) {
+		return Err(bad_request_error("hole cards should be two cards"));
+	}
+	let mut used = CardSet::EMPTY;
+	for &card in hands.iter().flatten().chain(board).chain(dead) {
+		if !used.insert(card) {
+			return Err(bad_request_error(format!("card used twice: {card}")));
+		}
+	}
+
+	let ranges: Vec<_> = hands
+		.iter()
+		.map(|hand| Range::from(CardSet::from(&hand[..])))
+		.collect();
+	calc_range_equity(&ranges, board, dead, options)
+}
+
+/// Calculate the equities of the ranges against each other
+///
+/// Combos holding a card of the board or a dead card are removed, and so are
+/// the deals where two players would hold the same card. Every deal counts in
+/// proportion to the weights of its combos. A hand against a range is a range
+/// of one combo, see [`Range::from`].
+///
+/// # Errors
+///
+/// Return bad request error if the board is more than five cards, a card is
+/// used twice, or no deal of the ranges is possible
+pub fn calc_range_equity(
+	ranges: &[Range],
+	board: &[Card],
+	dead: &[Card],
+	options: &EquityOptions,
+) -> Result<EquityResult> {
+	if ranges.is_empty() {
+		return Err(bad_request_error("there should be at least one hand"));
+	}
+	if board.len() > 5 {
+		return Err(bad_request_error("board should be at most five cards"));
+	}
+
+	let mut used = CardSet::EMPTY;
+	for &card in board.iter().chain(dead) {
+		if !used.insert(card) {
+			return Err(bad_request_error(format!("card used twice: {card}")));
+		}
+	}
+	let mut combos = Vec::with_capacity(ranges.len());
+	for range in ranges {
+		let mut range = range.clone();
+		range.remove_cards(used);
+		range.combos.retain(|combo| combo.weight > 0.0);
+		if range.is_empty() {
+			return Err(bad_request_error(format!(
+				"no combo left in range {}",
+				combos.len() + 1
+			)));
+		}
+		combos.push(range.combos);
+	}
+
+	let deck: Vec<_> = Card::new_sorted()
+		.into_iter()
+		.filter(|&card| !used.contains(card))
+		.collect();
+	let missing = 5 - board.len();
+	if deck.len() < missing + 2 * ranges.len() {
+		return Err(bad_request_error("not enough cards to complete the board"));
+	}
+
+	let board = CardSet::from(board);
+	let mut tally = Tally::new(ranges.len());
+
+	let deals = combos.iter().fold(1u128, |count, combos| {
+		count.saturating_mul(combos.len() as u128)
+	});
+	let runouts = combinations(deck.len() - 2 * ranges.len(), missing);
+	let exhaustive = deals.saturating_mul(runouts) <= options.max_exhaustive as u128;
+	if exhaustive {
+		for_each_deal(&combos, &mut |hands, weight| {
+			let rest: Vec<_> = deck
+				.iter()
+				.copied()
+				.filter(|&card| hands.iter().all(|hand| !hand.contains(card)))
+				.collect();
+			for_each_combination(&rest, missing, &mut |runout| {
+				tally.add(hands, board | runout, weight);
+			});
+		});
+	} else {
+		let mut rng = StdRng::seed_from_u64(options.seed);
+		let rest = CardSet::from(&deck[..]);
+		let pickers: Vec<_> = combos.iter().map(|combos| Picker::new(combos)).collect();
+		let mut hands = vec![CardSet::EMPTY; ranges.len()];
+		for _ in 0..options.samples {
+			if !deal_hands(&pickers, &mut hands, &mut rng) {
+				return Err(bad_request_error("no deal of the ranges is possible"));
+			}
+			let mut rest = rest;
+			for &hand in &hands {
+				rest -= hand;
+			}
+			let mut runout = CardSet::EMPTY;
+			for _ in 0..missing {
+				runout.insert(rest.deal(&mut rng).unwrap());
+			}
+			// picking by weight already accounts for the weights
+			tally.add(&hands, board | runout, 1.0);
+		}
+	}
+
+	if tally.weight == 0.0 {
+		return Err(bad_request_error("no deal of the ranges is possible"));
+	}
+
+	Ok(tally.result(exhaustive))
+}
+
+/// Wins and ties of every player over the boards
+///
+/// Every board counts for the weight of the deal of the hands.
+struct Tally {
+	wins: Vec<f64>,
+	ties: Vec<f64>,
+	/// Pots won, ties are split
+	shares: Vec<f64>,
+	boards: usize,
+	weight: f64,
+}
+
+impl Tally {
+	fn new(players: usize) -> Self {
+		Self {
+			wins: vec![0.0; players],
+			ties: vec![0.0; players],
+			shares: vec![0.0; players],
+			boards: 0,
+			weight: 0.0,
+		}
+	}
+
+	fn add(&mut self, hands: &[CardSet], board: CardSet, weight: f64) {
+		let common: Vec<_> = board.iter().collect();
+		let strengths: Vec<_> = hands
+			.iter()
+			.map(|hand| Hand::calc_best_hand(&common, &hand.iter().collect::<Vec<_>>()))
+			.collect();
+		let best = strengths.iter().max().unwrap();
+		let winners = strengths.iter().filter(|&s| s == best).count();
+		for (p, strength) in strengths.iter().enumerate() {
+			if strength != best {
+				continue;
+			}
+			if winners == 1 {
+				self.wins[p] += weight;
+			} else {
+				self.ties[p] += weight;
+			}
+			self.shares[p] += weight / winners as f64;
+		}
+		self.boards += 1;
+		self.weight += weight;
+	}
+
+	fn result(self, exhaustive: bool) -> EquityResult {
+		let percent = |count: f64| count * 100.0 / self.weight;
+		EquityResult {
+			players: (0..self.wins.len())
+				.map(|p| Equity {
+					win: percent(self.wins[p]),
+					tie: percent(self.ties[p]),
+					equity: percent(self.shares[p]),
+				})
+				.collect(),
+			boards: self.boards,
+			exhaustive,
+		}
+	}
+}
+
+/// Pick combos of a range at random in proportion to their weights
+struct Picker<'a> {
+	combos: &'a [Combo],
+	/// Running totals of the weights
+	totals: Vec<f64>,
+}
+
+impl<'a> Picker<'a> {
+	fn new(combos: &'a [Combo]) -> Self {
+		let totals = combos
+			.iter()
+			.scan(0.0, |total, combo| {
+				*total += combo.weight;
+				Some(*total)
+			})
+			.collect();
+		Self { combos, totals }
+	}
+
+	fn pick(&self, rng: &mut StdRng) -> CardSet {
+		let total = self.totals.last().unwrap();
+		let r = rng.random::<f64>() * total;
+		let i = self.totals.partition_point(|&t| t <= r);
+		self.combos[i.min(self.combos.len() - 1)].cards
+	}
+}
+
+/// Deal a combo of every range to the hands, dealing again when two combos
+/// share a card
+///
+/// Return false if no deal was found after many tries.
+fn deal_hands(pickers: &[Picker], hands: &mut [CardSet], rng: &mut StdRng) -> bool {
+	const MAX_TRIES: usize = 10_000;
+
+	'tries: for _ in 0..MAX_TRIES {
+		let mut used = CardSet::EMPTY;
+		for (hand, picker) in hands.iter_mut().zip(pickers) {
+			*hand = picker.pick(rng);
+			if !hand.is_disjoint(used) {
+				continue 'tries;
+			}
+			used |= *hand;
+		}
+		return true;
+	}
+
+	false
+}
+
+/// Call `f` with every deal of one combo of each range without a card held
+/// twice, and the product of the weights of the combos
+fn for_each_deal(ranges: &[Vec<Combo>], f: &mut impl FnMut(&[CardSet], f64)) {
+	fn deal(
+		ranges: &[Vec<Combo>],
+		hands: &mut Vec<CardSet>,
+		used: CardSet,
+		weight: f64,
+		f: &mut impl FnMut(&[CardSet], f64),
+	) {
+		let Some(combos) = ranges.get(hands.len()) else {
+			f(hands, weight);
+			return;
+		};
+		for combo in combos {
+			if combo.cards.is_disjoint(used) {
+				hands.push(combo.cards);
+				deal(ranges, hands, used | combo.cards, weight * combo.weight, f);
+				hands.pop();
+			}
+		}
+	}
+	deal(ranges, &mut Vec::new(), CardSet::EMPTY, 1.0, f);
+}
+
+/// Count of the ways to choose `k` of `n`
+fn combinations(n: usize, k: usize) -> u128 {
+	(0..k).fold(1, |count, i| count * (n - i) as u128 / (i + 1) as u128)
+}
+
+/// Call `f` with every set of `k` of the cards
+fn for_each_combination(cards: &[Card], k: usize, f: &mut impl FnMut(CardSet)) {
+	fn choose(cards: &[Card], k: usize, chosen: CardSet, f: &mut impl FnMut(CardSet)) {
+		if k == 0 {
+			f(chosen);
+			return;
+		}
+		for i in 0..=cards.len() - k {
+			choose(&cards[i + 1..], k - 1, chosen | CardSet::from(cards[i]), f);
+		}
+	}
+	choose(cards, k, CardSet::EMPTY, f);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_cards(cards: &str) -> Vec<Card> {
+		if cards.is_empty() {
+			return Vec::new();
+		}
+		cards.split(' ').map(Card::parse).collect()
+	}
+
+	fn equity(hands: &[&str], board: &str, dead: &str) -> EquityResult {
+		let hands: Vec<_> = hands.iter().map(|hand| parse_cards(hand)).collect();
+		calc_equity(
+			&hands,
+			&parse_cards(board),
+			&parse_cards(dead),
+			&EquityOptions::default(),
+		)
+		.unwrap()
+	}
+
+	#[test]
+	fn test_river() {
+		let result = equity(&["SA HA", "SK HK"], "C2 D7 C9 S3 H8", "");
+		assert!(result.exhaustive);
+		assert_eq!(result.boards, 1);
+		assert_eq!(result.players[0].win, 100.0);
+		assert_eq!(result.players[1].equity, 0.0);
+
+		let result = equity(&["SA H2", "HA S2"], "C2 D7 C9 S3 H8", "");
+		assert_eq!(result.players[0].tie, 100.0);
+		assert_eq!(result.players[0].equity, 50.0);
+	}
+
+	#[test]
+	fn test_turn() {
+		// Kings need one of the two kings left in 44 cards
+		let result = equity(&["SA HA", "SK HK"], "C2 D7 C9 S3", "");
+		assert_eq!(result.boards, 44);
+		assert!((result.players[1].win - 2.0 / 44.0 * 100.0).abs() < 1e-9);
+
+		// and none is left if they are dead
+		let result = equity(&["SA HA", "SK HK"], "C2 D7 C9 S3", "DK CK");
+		assert_eq!(result.boards, 42);
+		assert_eq!(result.players[0].win, 100.0);
+	}
+
+	#[test]
+	fn test_flop_against_hand() {
+		let hands = [
+			parse_cards("SA HA"),
+			parse_cards("SK HK"),
+			parse_cards("D9 DT"),
+		];
+		let board = parse_cards("C2 D7 D8");
+		let result = calc_equity(&hands, &board, &[], &EquityOptions::default()).unwrap();
+		assert!(result.exhaustive);
+
+		// count the winners with the hand comparison
+		let mut wins = [0; 3];
+		let deck: Vec<_> = Card::new_sorted()
+			.into_iter()
+			.filter(|c| !hands.iter().flatten().chain(&board).any(|d| d == c))
+			.collect();
+		for i in 0..deck.len() {
+			for j in i + 1..deck.len() {
+				let mut common = board.clone();
+				common.extend([deck[i], deck[j]]);
+				let best: Vec<_> = hands
+					.iter()
+					.map(|hand| Hand::calc_best_hand(&common, hand))
+					.collect();
+				let max = best.iter().max().unwrap();
+				if best.iter().filter(|&h| h == max).count() == 1 {
+					wins[best.iter().position(|h| h == max).unwrap()] += 1;
+				}
+			}
+		}
+
+		for (p, equity) in result.players.iter().enumerate() {
+			let expected = f64::from(wins[p]) * 100.0 / result.boards as f64;
+			assert!((equity.win - expected).abs() < 1e-9);
+		}
+		let total: f64 = result.players.iter().map(|e| e.equity).sum();
+		assert!((total - 100.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_monte_carlo() {
+		let options = EquityOptions {
+			samples: 20_000,
+			seed: 42,
+			..EquityOptions::default()
+		};
+		let hands = [parse_cards("SA HA"), parse_cards("SK HK")];
+		let result = calc_equity(&hands, &[], &[], &options).unwrap();
+		assert!(!result.exhaustive);
+		assert_eq!(result.boards, 20_000);
+		// aces are about 82% against kings
+		assert!((result.players[0].equity - 82.0).abs() < 1.5);
+
+		let again = calc_equity(&hands, &[], &[], &options).unwrap();
+		assert_eq!(again.players, result.players);
+	}
+
+	#[test]
+	fn test_hand_against_range() {
+		let options = EquityOptions::default();
+		let board = parse_cards("C2 D7 C9 S3");
+		let hand = Range::from(CardSet::from(&parse_cards("SA HA")[..]));
+
+		// a range of one hand is the same as the hand
+		let kings: Range = "SK HK".parse::<CardSet>().unwrap().into();
+		let result = calc_range_equity(&[hand.clone(), kings], &board, &[], &options).unwrap();
+		let expected = equity(&["SA HA", "SK HK"], "C2 D7 C9 S3", "");
+		assert_eq!(result.players, expected.players);
+
+		// against the 6 combos of kings, each winning with 2 of 44 rivers
+		let kings = Range::parse("KK").unwrap();
+		let result = calc_range_equity(&[hand.clone(), kings], &board, &[], &options).unwrap();
+		assert!(result.exhaustive);
+		assert_eq!(result.boards, 6 * 44);
+		assert!((result.players[1].win - 2.0 / 44.0 * 100.0).abs() < 1e-9);
+
+		// the combos of aces left are weighted
+		let range = Range::parse("AA:0.5, KK").unwrap();
+		let result = calc_range_equity(&[hand, range], &board, &[], &options).unwrap();
+		assert_eq!(result.boards, 7 * 44);
+		let kings = 6.0 * 2.0 / 44.0;
+		assert!((result.players[1].win - kings / 6.5 * 100.0).abs() < 1e-9);
+		assert!((result.players[0].tie - 0.5 / 6.5 * 100.0).abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_range_against_range() {
+		let options = EquityOptions {
+			samples: 20_000,
+			seed: 42,
+			..EquityOptions::default()
+		};
+		let ranges = [
+			Range::parse("QQ+").unwrap(),
+			Range::parse("AKs, AKo").unwrap(),
+		];
+		let result = calc_range_equity(&ranges, &[], &[], &options).unwrap();
+		assert!(!result.exhaustive);
+		let total: f64 = result.players.iter().map(|e| e.equity).sum();
+		assert!((total - 100.0).abs() < 1e-9);
+		// QQ+ is about 68% against AK
+		assert!((result.players[0].equity - 68.0).abs() < 2.0);
+
+		// aces against aces share the pot most of the time
+		let ranges = [Range::parse("AA").unwrap(), Range::parse("AA").unwrap()];
+		let board = parse_cards("C2 D7 C9 S3 H8");
+		let result = calc_range_equity(&ranges, &board, &[], &options).unwrap();
+		assert!(result.exhaustive);
+		assert_eq!(result.boards, 6);
+		assert_eq!(result.players[0].tie, 100.0);
+
+		let ranges = [Range::parse("AA").unwrap(), Range::parse("AK").unwrap()];
+		let board = parse_cards("SA HA DA");
+		assert!(calc_range_equity(&ranges, &board, &[], &options).is_err());
+	}
+
+	#[test]
+	fn test_invalid() {
+		let options = EquityOptions::default();
+		let hands = [parse_cards("SA HA"), parse_cards("SA HK")];
+		assert!(calc_equity(&hands, &[], &[], &options).is_err());
+		assert!(calc_equity(&[parse_cards("SA")], &[], &[], &options).is_err());
+		assert!(calc_equity(&[], &[], &[], &options).is_err());
+		let board = parse_cards("C2 D7 C9 S3 H8 H9");
+		assert!(calc_equity(&hands[..1], &board, &[], &options).is_err());
+	}
+}
@@ -0,0 +1,340 @@
+//! Lookup-table evaluator of 5, 6 and 7 card hands
+//!
+//! Every hand is mapped to a strength from 1 to [`MAX_STRENGTH`], the higher
+//! the better, and hands of the same strength are tied. There are 7462
+//! strengths, one for each class of five cards hands.
+//!
+//! Flushes are looked up by the bitmask of the ranks in the flush suit, other
+//! hands by the perfect hash of the count of each rank. With at most seven
+//! cards, a hand with a flush can't have a full house or four of a kind, so
+//! the flush is always the best hand if there is one.
+
+use std::{collections::HashMap, sync::LazyLock};
+
+use super::{Card, CardSet, Kind, Suit};
+
+/// Strength of the best hand, a royal flush
+pub const MAX_STRENGTH: u16 = 7462;
+
+/// Count of ranks
+const RANKS: usize = 13;
+/// Max count of cards evaluated
+const MAX_CARDS: usize = 7;
+
+/// Lowest strength of each kind from high card to royal flush
+const KIND_STRENGTHS: [u16; 10] = [1, 1278, 4138, 4996, 5854, 5864, 7141, 7297, 7453, 7462];
+
+/// Number of ways to put `k` cards into the lowest `n` ranks, at most four of
+/// a rank
+static COUNTS: LazyLock<[[usize; MAX_CARDS + 1]; RANKS + 1]> = LazyLock::new(|| {
+	let mut counts = [[0; MAX_CARDS + 1]; RANKS + 1];
+	counts[0][0] = 1;
+	for n in 1..=RANKS {
+		for k in 0..=MAX_CARDS {
+			counts[n][k] = (0..=k.min(4)).map(|c| counts[n - 1][k - c]).sum();
+		}
+	}
+	counts
+});
+
+struct Tables {
+	/// Strength of the best flush by the bitmask of the ranks in the suit
+	flush: Vec<u16>,
+	/// Strength of the other hands of `k` cards by the hash of rank counts
+	unsuited: [Vec<u16>; MAX_CARDS + 1],
+}
+
+static TABLES: LazyLock<Tables> = LazyLock::new(Tables::new);
+
+impl Tables {
+	fn new() -> Self {
+		let strengths = five_card_strengths();
+
+		let mut flush = vec![0; 1 << RANKS];
+		for (mask, strength) in flush.iter_mut().enumerate() {
+			let ranks: Vec<_> = (0..RANKS).rev().filter(|r| mask & (1 << r) != 0).collect();
+			if (5..=MAX_CARDS).contains(&ranks.len()) {
+				*strength = best_of(&ranks, |five| strengths[&class_key(five, true)]);
+			}
+		}
+
+		let mut unsuited: [Vec<u16>; MAX_CARDS + 1] = Default::default();
+		for (k, table) in unsuited.iter_mut().enumerate().skip(5) {
+			*table = vec![0; COUNTS[RANKS][k]];
+			for_each_counts(k, &mut |counts| {
+				let ranks: Vec<_> = (0..RANKS)
+					.rev()
+					.flat_map(|r| std::iter::repeat_n(r, counts[r] as usize))
+					.collect();
+				table[hash(counts, k)] = best_of(&ranks, |five| strengths[&class_key(five, false)]);
+			});
+		}
+
+		Self { flush, unsuited }
+	}
+}
+
+/// Evaluate the strength of five to seven cards
+///
+/// # Panics
+///
+/// Will panic if the count of `cards` is not 5, 6 or 7
+#[must_use]
+pub fn evaluate(cards: &[Card]) -> u16 {
+	let set = CardSet::from(cards);
+	assert_eq!(set.len(), cards.len(), "duplicate cards");
+	evaluate_set(set)
+}
+
+/// Evaluate the strength of a set of five to seven cards
+///
+/// # Panics
+///
+/// Will panic if the count of `cards` is not 5, 6 or 7
+#[must_use]
+pub fn evaluate_set(cards: CardSet) -> u16 {
+	assert!((5..=MAX_CARDS).contains(&cards.len()));
+	let tables = &*TABLES;
+
+	let mut counts = [0; RANKS];
+	for suit in Suit::iter() {
+		let mut ranks = cards.suit_ranks(suit);
+		if ranks.count_ones() >= 5 {
+			return tables.flush[ranks as usize];
+		}
+		while ranks != 0 {
+			counts[ranks.trailing_zeros() as usize] += 1;
+			ranks &= ranks - 1;
+		}
+	}
+	tables.unsuited[cards.len()][hash(&counts, cards.len())]
+}
+
+/// Kind of the hands of the strength
+///
+/// # Panics
+///
+/// Will panic if `strength` is not from 1 to [`MAX_STRENGTH`]
+#[must_use]
+pub fn strength_kind(strength: u16) -> Kind {
+	assert!((1..=MAX_STRENGTH).contains(&strength));
+	let kinds = [
+		Kind::HighCard,
+		Kind::Pair,
+		Kind::TwoPairs,
+		Kind::ThreeOfAKind,
+		Kind::Straight,
+		Kind::Flush,
+		Kind::FullHouse,
+		Kind::FourOfAKind,
+		Kind::StraightFlush,
+		Kind::RoyalFlush,
+	];
+	let i = KIND_STRENGTHS.partition_point(|&s| s <= strength);
+	kinds.into_iter().nth(i - 1).unwrap()
+}
+
+/// Index of the rank counts among all counts of `k` cards in lexicographic
+/// order, from 0 to the count of them
+fn hash(counts: &[u8; RANKS], mut k: usize) -> usize {
+	let counts_table = &*COUNTS;
+	let mut index = 0;
+	for (i, &count) in counts.iter().enumerate() {
+		let rest = RANKS - i - 1;
+		for c in 0..count as usize {
+			index += counts_table[rest][k - c];
+		}
+		k -= count as usize;
+		if k == 0 {
+			break;
+		}
+	}
+	index
+}
+
+/// Call `f` with every rank counts of `k` cards, at most four of a rank
+fn for_each_counts(k: usize, f: &mut impl FnMut(&[u8; RANKS])) {
+	fn fill(counts: &mut [u8; RANKS], i: usize, k: usize, f: &mut impl FnMut(&[u8; RANKS])) {
+		if i == RANKS {
+			if k == 0 {
+				f(counts);
+			}
+			return;
+		}
+		for c in 0..=k.min(4) {
+			counts[i] = c as u8;
+			fill(counts, i + 1, k - c, f);
+		}
+		counts[i] = 0;
+	}
+	fill(&mut [0; RANKS], 0, k, f);
+}
+
+/// The best strength of every five of the ranks sorted from high to low
+fn best_of(ranks: &[usize], strength: impl Fn(&[usize; 5]) -> u16) -> u16 {
+	let n = ranks.len();
+	let mut best = 0;
+	for skip in 0..(1usize << n) {
+		if skip.count_ones() as usize != n - 5 {
+			continue;
+		}
+		let mut five = [0; 5];
+		let picked = (0..n).filter(|i| skip & (1 << i) == 0).map(|i| ranks[i]);
+		for (slot, rank) in five.iter_mut().zip(picked) {
+			*slot = rank;
+		}
+		best = best.max(strength(&five));
+	}
+	best
+}
+
+/// Key of the class of five cards, ranks are sorted from high to low
+///
+/// Classes are ordered by the kind, then by the ranks of the groups from the
+/// largest group, and by the ranks in groups of the same size from high to
+/// low.
+fn class_key(ranks: &[usize; 5], flush: bool) -> (Kind, [usize; 5]) {
+	let mut groups: Vec<(usize, usize)> = Vec::new();
+	for &rank in ranks {
+		match groups.iter_mut().find(|(r, _)| *r == rank) {
+			Some((_, count)) => *count += 1,
+			None => groups.push((rank, 1)),
+		}
+	}
+	groups.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+
+	let distinct = groups.len() == 5;
+	// 5 high straight, the ace plays low
+	let wheel = *ranks == [12, 3, 2, 1, 0];
+	let straight = distinct && (ranks[0] - ranks[4] == 4 || wheel);
+	let mut order = [0; 5];
+	for (slot, rank) in order
+		.iter_mut()
+		.zip(groups.iter().flat_map(|&(r, c)| std::iter::repeat_n(r, c)))
+	{
+		*slot = rank;
+	}
+	if wheel {
+		order = [3, 2, 1, 0, 0];
+	}
+
+	let kind = match (groups[0].1, groups.get(1).map(|g| g.1)) {
+		_ if straight && flush && ranks[4] == 8 => Kind::RoyalFlush,
+		_ if straight && flush => Kind::StraightFlush,
+		(4, _) => Kind::FourOfAKind,
+		(3, Some(2)) => Kind::FullHouse,
+		_ if flush => Kind::Flush,
+		_ if straight => Kind::Straight,
+		(3, _) => Kind::ThreeOfAKind,
+		(2, Some(2)) => Kind::TwoPairs,
+		(2, _) => Kind::Pair,
+		_ => Kind::HighCard,
+	};
+	(kind, order)
+}
+
+/// Strengths of all classes of five cards by their keys
+fn five_card_strengths() -> HashMap<(Kind, [usize; 5]), u16> {
+	let mut keys = Vec::new();
+	for_each_counts(5, &mut |counts| {
+		let mut ranks = [0; 5];
+		let sorted = (0..RANKS)
+			.rev()
+			.flat_map(|r| std::iter::repeat_n(r, counts[r] as usize));
+		for (slot, rank) in ranks.iter_mut().zip(sorted) {
+			*slot = rank;
+		}
+		keys.push(class_key(&ranks, false));
+		if counts.iter().all(|&c| c <= 1) {
+			keys.push(class_key(&ranks, true));
+		}
+	});
+	keys.sort();
+
+	keys.into_iter()
+		.enumerate()
+		.map(|(i, key)| (key, i as u16 + 1))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+	use super::*;
+	use crate::Hand;
+
+	fn parse_cards(cards: &str) -> Vec<Card> {
+		cards.split(' ').map(Card::parse).collect()
+	}
+
+	#[test]
+	fn test_evaluate() {
+		assert_eq!(evaluate(&parse_cards("SA SK SQ SJ ST")), MAX_STRENGTH);
+		assert_eq!(evaluate(&parse_cards("S7 H5 D4 C3 S2")), 1);
+		assert_eq!(evaluate(&parse_cards("SA H2 D3 C4 S5 HK DK")), 5854);
+		assert_eq!(
+			evaluate(&parse_cards("SA HA DA CA S2 H2 D2")),
+			evaluate(&parse_cards("SA HA DA CA S2"))
+		);
+		assert_eq!(
+			strength_kind(evaluate(&parse_cards("SA H2 D3 C4 S5 HK DK"))),
+			Kind::Straight
+		);
+		assert_eq!(
+			strength_kind(evaluate(&parse_cards("S9 S8 S7 S6 S5 SA"))),
+			Kind::StraightFlush
+		);
+	}
+
+	#[test]
+	fn test_all_five_cards() {
+		let deck = Card::new_sorted();
+		let mut kinds = HashMap::new();
+		let mut strengths = std::collections::HashSet::new();
+		for a in 0..52 {
+			for b in a + 1..52 {
+				for c in b + 1..52 {
+					for d in c + 1..52 {
+						for e in d + 1..52 {
+							let strength = evaluate(&[deck[a], deck[b], deck[c], deck[d], deck[e]]);
+							*kinds.entry(strength_kind(strength)).or_insert(0) += 1;
+							strengths.insert(strength);
+						}
+					}
+				}
+			}
+		}
+
+		assert_eq!(strengths.len(), MAX_STRENGTH as usize);
+		assert_eq!(kinds[&Kind::RoyalFlush], 4);
+		assert_eq!(kinds[&Kind::StraightFlush], 36);
+		assert_eq!(kinds[&Kind::FourOfAKind], 624);
+		assert_eq!(kinds[&Kind::FullHouse], 3744);
+		assert_eq!(kinds[&Kind::Flush], 5108);
+		assert_eq!(kinds[&Kind::Straight], 10200);
+		assert_eq!(kinds[&Kind::ThreeOfAKind], 54912);
+		assert_eq!(kinds[&Kind::TwoPairs], 123_552);
+		assert_eq!(kinds[&Kind::Pair], 1_098_240);
+		assert_eq!(kinds[&Kind::HighCard], 1_302_540);
+	}
+
+	#[test]
+	fn test_cross_check() {
+		let mut rng = StdRng::seed_from_u64(7462);
+		let mut deck = Card::new_sorted();
+		for _ in 0..2000 {
+			deck.shuffle(&mut rng);
+			let (a, b) = (&deck[..7], &deck[7..14]);
+			let (hand_a, hand_b) = (
+				Hand::calc_best_hand(&a[..5], &a[5..]),
+				Hand::calc_best_hand(&b[..5], &b[5..]),
+			);
+			let (strength_a, strength_b) = (evaluate(a), evaluate(b));
+
+			assert_eq!(strength_kind(strength_a), hand_a.kind);
+			assert_eq!(strength_a.cmp(&strength_b), hand_a.cmp(&hand_b));
+			assert_eq!(evaluate(a), evaluate(&hand_a.cards));
+		}
+	}
+}
@@ -3,8 +3,6 @@ use std::fmt::Display;
 use rusqlite::{ToSql, types::FromSql};
 use serde::{Deserialize, Serialize};
 
-use super::Room;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Ord)]
 pub enum Round {
 	PreFlop,
@@ -85,20 +83,26 @@ pub struct Game {
 	pub pot: usize,
 	/// Current player's position
 	pub position: usize,
-	/// Position of the first raise player
-	pub raise_position: usize,
+	/// Size of the last full bet or raise in this round
+	pub min_raise: usize,
+	/// Bitmask of positions which have acted since the last full raise
+	pub acted: usize,
+	/// Position of the last player who bet or raised in the last betting round
+	pub aggressor: Option<usize>,
 }
 
 impl Game {
 	#[must_use]
-	pub fn new(id: usize, room_id: usize, sb: usize) -> Self {
+	pub fn new(id: usize, room_id: usize, sb: usize, big_blind: usize) -> Self {
 		Self {
 			id,
 			room_id,
 			round: Round::PreFlop,
 			pot: 0,
 			position: sb,
-			raise_position: sb,
+			min_raise: big_blind,
+			acted: 0,
+			aggressor: None,
 		}
 	}
 
@@ -107,63 +111,44 @@ impl Game {
 		self.round == Round::Over
 	}
 
-	/// Correct player position
-	pub fn correct(&mut self, room: &Room) {
-		let mut p;
-		for i in 0..Room::MAX_SEATS {
-			p = (self.position + i) % Room::MAX_SEATS;
-			if let Some(seat) = &room.seats[p] {
-				if seat.fold || seat.allin() {
-					continue;
-				}
-				self.position = p;
-				return;
-			}
-		}
+	/// Whether the player has acted since the last full raise
+	#[must_use]
+	pub fn has_acted(&self, position: usize) -> bool {
+		self.acted & (1 << position) != 0
 	}
 
-	/// Pass control to the next player
-	pub fn pass(&mut self, room: &Room) {
-		self.position += 1;
-		self.correct(room);
-	}
-
-	/// Update round if condition meet
-	///
-	/// # Return
-	///
-	/// Return ture if round changed
-	pub fn update(&mut self, room: &Room) -> bool {
-		// all fold except one or all remaining allin
-		if room.player_count() == 1 || room.all_allin() {
-			self.round = Round::Over;
-			return true;
-		}
-
-		// deal logic
-		if self.position == self.raise_position {
-			self.round = self.round.next_round();
-			return true;
-		}
-
-		false
+	/// Mark the player as acted
+	pub fn set_acted(&mut self, position: usize) {
+		self.acted |= 1 << position;
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameResult {
 	pub game_id: usize,
 	pub guest_id: usize,
 	pub diff: isize,
+	/// Whether the guest won (or shared) any pot
+	pub winner: bool,
+	/// Chips returned to the guest because nobody called them
+	pub uncalled: usize,
 }
 
 impl GameResult {
 	#[must_use]
-	pub fn new(game_id: usize, guest_id: usize, diff: isize) -> Self {
+	pub fn new(
+		game_id: usize,
+		guest_id: usize,
+		diff: isize,
+		winner: bool,
+		uncalled: usize,
+	) -> Self {
 		Self {
 			game_id,
 			guest_id,
 			diff,
+			winner,
+			uncalled,
 		}
 	}
 }
@@ -1,6 +1,6 @@
 use super::{Card, Rank};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Kind {
 	HighCard,
 	Pair,
@@ -80,55 +80,20 @@ impl Hand {
 	}
 
 	/// Make cards easier to compare from first to last
+	///
+	/// Cards of larger groups of the same rank come first, then higher ranks
+	/// first, and the ace of the lowest straight comes last.
 	fn normalized(mut self) -> Self {
-		if Self::is_lowest_straight(&self.cards) || Self::is_last_four_of_a_kind(&self.cards) {
-			self.cards.swap(0, 4);
-			self.cards[0..4].sort();
-			self.cards[0..4].reverse();
-		} else if self.kind == Kind::FullHouse {
-			if self.cards[2].rank == self.cards[3].rank && self.cards[2].rank == self.cards[4].rank
-			{
-				self.cards.swap(0, 3);
-				self.cards.swap(1, 4);
-				self.cards[0..3].sort();
-				self.cards[0..3].reverse();
-			}
-		} else if self.kind == Kind::ThreeOfAKind {
-			// a a a b c
-			// a b b b c
-			// a b c c c
-			for i in 0..2 {
-				if self.cards[i].rank != self.cards[2].rank {
-					self.cards.swap(i, 4 - i);
-					break;
-				}
-			}
-			self.cards[0..3].sort();
-			self.cards[0..3].reverse();
-			self.cards[3..5].sort();
-			self.cards[3..5].reverse();
-		} else if self.kind == Kind::TwoPairs {
-			// abbcc
-			// bbacc
-			// bbcca
-			if self.cards[0].rank != self.cards[1].rank {
-				self.cards.swap(0, 4);
-			} else if self.cards[2].rank != self.cards[1].rank {
-				self.cards.swap(2, 4);
-			}
-			self.cards[0..4].sort();
-			self.cards[0..4].reverse();
-		} else if self.kind == Kind::Pair {
-			// find pair
-			for i in 0..4 {
-				if self.cards[i].rank == self.cards[i + 1].rank {
-					self.cards.swap(0, i);
-					self.cards.swap(1, i + 1);
-					break;
-				}
-			}
+		if Self::is_lowest_straight(&self.cards) {
+			self.cards.rotate_left(1);
+			return self;
 		}
 
+		let ranks: Vec<_> = self.cards.iter().map(|c| c.rank).collect();
+		let count = |rank| ranks.iter().filter(|&&r| r == rank).count();
+		self.cards
+			.sort_by_key(|card| std::cmp::Reverse((count(card.rank), card.rank)));
+
 		self
 	}
 
@@ -311,6 +276,10 @@ mod tests {
 		assert_eq!(h1.kind, Kind::Pair);
 		assert_eq!(h2.kind, Kind::Pair);
 		assert!(h1 > h2);
+
+		let h1 = parse_hand(["SK", "HQ", "C9", "S7", "H7"]);
+		let h2 = parse_hand(["SK", "HJ", "CT", "D7", "C7"]);
+		assert!(h1 > h2);
 	}
 
 	#[test]
@@ -330,6 +299,10 @@ mod tests {
 		assert_eq!(h1.kind, Kind::ThreeOfAKind);
 		assert_eq!(h2.kind, Kind::ThreeOfAKind);
 		assert!(h1 > h2);
+
+		let h1 = parse_hand(["SA", "H9", "C9", "S9", "H2"]);
+		let h2 = parse_hand(["SK", "HQ", "C9", "S9", "H9"]);
+		assert!(h1 > h2);
 	}
 
 	#[test]
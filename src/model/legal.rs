@@ -0,0 +1,172 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Game, Room};
+
+/// Smallest and largest amount of a bet or raise, both inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BetRange {
+	pub min: usize,
+	pub max: usize,
+}
+
+/// Actions the current player may take, with exact amounts
+///
+/// Folding is always allowed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegalActions {
+	pub position: usize,
+	pub check: bool,
+	/// Chips to call, None if there is nothing to call
+	pub call: Option<usize>,
+	/// Amount to bet, None if somebody has bet in this round
+	pub bet: Option<BetRange>,
+	/// Total bet of the round to raise to, None if raising is not allowed
+	pub raise_to: Option<BetRange>,
+	/// Chips put in by going all-in, None if that would raise an action which
+	/// is not reopened
+	pub all_in: Option<usize>,
+}
+
+impl Game {
+	/// Legal actions of the current player
+	///
+	/// A player can't bet or raise with no more chips than the call, nor after
+	/// acting if the action is not reopened by a full raise. The smallest bet or
+	/// raise is capped by the stack, i.e. a short all-in. Going all-in is allowed
+	/// unless it would raise an action which is not reopened.
+	///
+	/// # Return
+	///
+	/// None if the game is over
+	#[must_use]
+	pub fn legal_actions(&self, room: &Room) -> Option<LegalActions> {
+		if self.is_over() {
+			return None;
+		}
+		let seat = room.seats[self.position].as_ref()?;
+		let max_bet = room.max_bet();
+		let to_call = room.call_amount(self.position);
+		let all_in = seat.round_bet + seat.stack;
+
+		let reopened = !self.has_acted(self.position);
+		let range = (seat.stack > to_call && reopened).then(|| BetRange {
+			min: (max_bet + self.min_raise).min(all_in),
+			max: all_in,
+		});
+
+		Some(LegalActions {
+			position: self.position,
+			check: to_call == 0,
+			call: (to_call > 0).then_some(to_call),
+			bet: range.filter(|_| max_bet == 0),
+			raise_to: range.filter(|_| max_bet > 0),
+			all_in: (seat.stack <= to_call || reopened).then_some(seat.stack),
+		})
+	}
+}
+
+impl Display for LegalActions {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "fold")?;
+		if self.check {
+			write!(f, ", check")?;
+		}
+		if let Some(call) = self.call {
+			write!(f, ", call {call}")?;
+		}
+		if let Some(bet) = self.bet {
+			write!(f, ", bet {}..={}", bet.min, bet.max)?;
+		}
+		if let Some(raise_to) = self.raise_to {
+			write!(f, ", raise to {}..={}", raise_to.min, raise_to.max)?;
+		}
+		if let Some(all_in) = self.all_in {
+			write!(f, ", allin {all_in}")?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Guest;
+
+	/// Room with players of `stacks` and `bets` in this round, from seat 0
+	fn new_room(stacks: &[usize], bets: &[usize]) -> Room {
+		let mut room = Room::new(1);
+		for (i, (&stack, &bet)) in stacks.iter().zip(bets).enumerate() {
+			room.insert(Guest::new(i + 1, "guest")).unwrap();
+			let seat = room.seats[i].as_mut().unwrap();
+			seat.stack = stack;
+			seat.bet = bet;
+			seat.round_bet = bet;
+		}
+		room
+	}
+
+	fn new_game(position: usize, min_raise: usize) -> Game {
+		let mut game = Game::new(1, 1, position, 2);
+		game.min_raise = min_raise;
+		game
+	}
+
+	#[test]
+	fn test_facing_bet() {
+		let room = new_room(&[99, 98, 100], &[1, 2, 0]);
+		let actions = new_game(2, 2).legal_actions(&room).unwrap();
+		assert_eq!(
+			actions,
+			LegalActions {
+				position: 2,
+				check: false,
+				call: Some(2),
+				bet: None,
+				raise_to: Some(BetRange { min: 4, max: 100 }),
+				all_in: Some(100),
+			}
+		);
+	}
+
+	#[test]
+	fn test_check_or_bet() {
+		let room = new_room(&[50, 50], &[0, 0]);
+		let actions = new_game(0, 2).legal_actions(&room).unwrap();
+		assert!(actions.check);
+		assert_eq!(actions.call, None);
+		assert_eq!(actions.bet, Some(BetRange { min: 2, max: 50 }));
+		assert_eq!(actions.raise_to, None);
+	}
+
+	#[test]
+	fn test_short_stack() {
+		// not enough chips for a full raise
+		let room = new_room(&[100, 5], &[10, 0]);
+		let actions = new_game(1, 10).legal_actions(&room).unwrap();
+		assert_eq!(actions.call, Some(5));
+		assert_eq!(actions.raise_to, None);
+
+		let room = new_room(&[100, 15], &[10, 0]);
+		let actions = new_game(1, 10).legal_actions(&room).unwrap();
+		assert_eq!(actions.raise_to, Some(BetRange { min: 15, max: 15 }));
+	}
+
+	#[test]
+	fn test_not_reopened() {
+		let room = new_room(&[90, 93, 0], &[10, 7, 12]);
+		let mut game = new_game(1, 5);
+		game.set_acted(1);
+		let actions = game.legal_actions(&room).unwrap();
+		assert_eq!(actions.call, Some(5));
+		assert_eq!(actions.raise_to, None);
+		assert_eq!(actions.all_in, None);
+
+		// going all-in for no more than the call is calling
+		let room = new_room(&[90, 4, 0], &[10, 7, 12]);
+		let actions = game.legal_actions(&room).unwrap();
+		assert_eq!(actions.call, Some(4));
+		assert_eq!(actions.all_in, Some(4));
+	}
+}
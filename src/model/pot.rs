@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use super::Room;
+
+/// Chips a seat has put into the pot during the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contribution {
+	pub position: usize,
+	pub chips: usize,
+	pub fold: bool,
+}
+
+impl Contribution {
+	#[must_use]
+	pub fn new(position: usize, chips: usize, fold: bool) -> Self {
+		Self {
+			position,
+			chips,
+			fold,
+		}
+	}
+}
+
+/// Chips returned to a player because nobody called them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uncalled {
+	pub position: usize,
+	pub chips: usize,
+}
+
+/// The main pot or a side pot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pot {
+	pub chips: usize,
+	/// Positions of players who can win this pot
+	pub eligible: Vec<usize>,
+}
+
+impl Pot {
+	/// Build the main pot and side pots from each seat's total contribution
+	///
+	/// A new side pot is created for every all-in level of the unfold players.
+	/// Chips of folded players go to the pots they reached, and the top
+	/// contribution which nobody matched is returned uncalled.
+	///
+	/// # Return
+	///
+	/// Pots from the main pot to the last side pot, and the uncalled chips
+	#[must_use]
+	pub fn build(contributions: &[Contribution]) -> (Vec<Self>, Option<Uncalled>) {
+		let mut chips: Vec<_> = contributions.iter().map(|c| c.chips).collect();
+
+		// return uncalled chips to the top contributor
+		let mut uncalled = None;
+		if let Some((i, top)) = contributions
+			.iter()
+			.enumerate()
+			.filter(|(_, c)| !c.fold)
+			.max_by_key(|(_, c)| c.chips)
+		{
+			let second = contributions
+				.iter()
+				.enumerate()
+				.filter(|&(j, _)| j != i)
+				.map(|(_, c)| c.chips)
+				.max()
+				.unwrap_or(0);
+			if top.chips > second {
+				chips[i] = second;
+				uncalled = Some(Uncalled {
+					position: top.position,
+					chips: top.chips - second,
+				});
+			}
+		}
+
+		let mut levels: Vec<_> = contributions
+			.iter()
+			.zip(&chips)
+			.filter(|(c, _)| !c.fold)
+			.map(|(_, &chips)| chips)
+			.filter(|&chips| chips > 0)
+			.collect();
+		levels.sort_unstable();
+		levels.dedup();
+
+		let mut pots = Vec::new();
+		let mut last = 0;
+		for level in levels {
+			let pot = Self {
+				chips: chips.iter().map(|&c| c.min(level) - c.min(last)).sum(),
+				eligible: contributions
+					.iter()
+					.zip(&chips)
+					.filter(|&(c, &chips)| !c.fold && chips >= level)
+					.map(|(c, _)| c.position)
+					.collect(),
+			};
+			pots.push(pot);
+			last = level;
+		}
+
+		// folded players may have put in more than any unfold player
+		let dead: usize = chips.iter().map(|&c| c.saturating_sub(last)).sum();
+		if dead > 0
+			&& let Some(pot) = pots.last_mut()
+		{
+			pot.chips += dead;
+		}
+
+		(pots, uncalled)
+	}
+}
+
+impl Room {
+	/// Contributions of all seated guests
+	#[must_use]
+	pub fn contributions(&self) -> Vec<Contribution> {
+		self.seats
+			.iter()
+			.enumerate()
+			.filter_map(|(i, s)| s.as_ref().map(|s| Contribution::new(i, s.bet, s.fold)))
+			.collect()
+	}
+}
+
+/// Stored breakdown of a pot after the game is over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PotResult {
+	pub game_id: usize,
+	/// 0 for the main pot, then side pots in order
+	pub number: usize,
+	pub chips: usize,
+	/// IDs of guests who could win this pot
+	pub eligible: Vec<usize>,
+	/// IDs of guests who won this pot and chips won by each
+	pub winners: Vec<(usize, usize)>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn build(contributions: &[(usize, usize, bool)]) -> (Vec<Pot>, Option<Uncalled>) {
+		let contributions: Vec<_> = contributions
+			.iter()
+			.map(|&(p, c, f)| Contribution::new(p, c, f))
+			.collect();
+		Pot::build(&contributions)
+	}
+
+	fn pot(chips: usize, eligible: &[usize]) -> Pot {
+		Pot {
+			chips,
+			eligible: eligible.to_vec(),
+		}
+	}
+
+	#[test]
+	fn test_single_pot() {
+		let (pots, uncalled) = build(&[(0, 100, false), (1, 100, false), (2, 40, true)]);
+		assert_eq!(pots, vec![pot(240, &[0, 1])]);
+		assert_eq!(uncalled, None);
+	}
+
+	#[test]
+	fn test_uncalled() {
+		let (pots, uncalled) = build(&[(0, 300, false), (1, 100, false)]);
+		assert_eq!(pots, vec![pot(200, &[0, 1])]);
+		assert_eq!(
+			uncalled,
+			Some(Uncalled {
+				position: 0,
+				chips: 200
+			})
+		);
+	}
+
+	#[test]
+	fn test_three_way_allin() {
+		let (pots, uncalled) = build(&[(0, 50, false), (3, 200, false), (5, 120, false)]);
+		assert_eq!(pots, vec![pot(150, &[0, 3, 5]), pot(140, &[3, 5])]);
+		assert_eq!(
+			uncalled,
+			Some(Uncalled {
+				position: 3,
+				chips: 80
+			})
+		);
+	}
+
+	#[test]
+	fn test_three_way_allin_with_fold() {
+		let (pots, uncalled) = build(&[
+			(0, 30, false),
+			(1, 80, true),
+			(2, 100, false),
+			(4, 100, false),
+		]);
+		assert_eq!(pots, vec![pot(120, &[0, 2, 4]), pot(190, &[2, 4])]);
+		assert_eq!(uncalled, None);
+	}
+
+	#[test]
+	fn test_four_way_allin() {
+		let (pots, uncalled) = build(&[
+			(1, 25, false),
+			(2, 100, false),
+			(6, 60, false),
+			(9, 100, false),
+		]);
+		assert_eq!(
+			pots,
+			vec![
+				pot(100, &[1, 2, 6, 9]),
+				pot(105, &[2, 6, 9]),
+				pot(80, &[2, 9])
+			]
+		);
+		assert_eq!(uncalled, None);
+	}
+
+	#[test]
+	fn test_four_way_allin_same_stack() {
+		let (pots, uncalled) = build(&[
+			(0, 40, false),
+			(1, 40, false),
+			(2, 500, false),
+			(3, 70, false),
+		]);
+		assert_eq!(pots, vec![pot(160, &[0, 1, 2, 3]), pot(60, &[2, 3])]);
+		assert_eq!(
+			uncalled,
+			Some(Uncalled {
+				position: 2,
+				chips: 430
+			})
+		);
+	}
+
+	#[test]
+	fn test_folded_over_contribution() {
+		let (pots, uncalled) = build(&[(0, 20, false), (1, 50, true), (2, 20, false)]);
+		assert_eq!(pots, vec![pot(90, &[0, 2])]);
+		assert_eq!(uncalled, None);
+	}
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result, bad_request_error};
+
+use super::{Card, CardSet, Rank, Suit};
+
+/// Two hole cards in a range
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Combo {
+	pub cards: CardSet,
+	/// Share of the combo played, from 0 to 1
+	pub weight: f64,
+}
+
+/// Hole cards a player may hold
+///
+/// Ranges are written in the common notation, separated by commas:
+///
+/// - `QQ`, `AKs`, `KQo` and `AK` for a pair, suited, offsuit and both
+/// - `QQ+` for QQ and higher pairs, `A5s+` for A5s up to AKs, and `76s+` for
+///   76s and the higher connectors with the same gap up to AKs
+/// - `QQ-88` and `A5s-A2s` for everything in between, both included
+/// - `AKs:0.5` for a weight other than 1
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+	pub combos: Vec<Combo>,
+}
+
+/// Suitedness of the hands of a range notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suitedness {
+	Suited,
+	Offsuit,
+	Any,
+}
+
+/// Ranks of a hand in a range notation, the high rank first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HandClass {
+	high: usize,
+	low: usize,
+	suited: Suitedness,
+}
+
+impl Range {
+	/// Parse a range like `QQ+, AKs, A5s-A2s, KQo, 76s+`
+	///
+	/// If a combo is written more than once, the last weight is used.
+	///
+	/// # Errors
+	///
+	/// Return bad request error if the notation is not valid
+	pub fn parse(range: &str) -> Result<Self> {
+		let mut combos: Vec<Combo> = Vec::new();
+		let mut indexes = HashMap::new();
+		for part in range.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+			let (notation, weight) = match part.split_once(':') {
+				Some((notation, weight)) => (notation.trim(), parse_weight(weight.trim())?),
+				None => (part, 1.0),
+			};
+			for class in expand(notation)? {
+				for cards in class.combos() {
+					let combo = Combo { cards, weight };
+					match indexes.get(&cards) {
+						Some(&i) => combos[i] = combo,
+						None => {
+							indexes.insert(cards, combos.len());
+							combos.push(combo);
+						}
+					}
+				}
+			}
+		}
+
+		Ok(Self { combos })
+	}
+
+	/// Count of the combos
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.combos.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.combos.is_empty()
+	}
+
+	/// Remove the combos holding any of the cards, like the board and dead cards
+	pub fn remove_cards(&mut self, cards: CardSet) {
+		self.combos.retain(|combo| combo.cards.is_disjoint(cards));
+	}
+}
+
+impl FromStr for Range {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		Self::parse(s)
+	}
+}
+
+impl From<CardSet> for Range {
+	/// Range of exactly the hole cards
+	fn from(cards: CardSet) -> Self {
+		Self {
+			combos: vec![Combo { cards, weight: 1.0 }],
+		}
+	}
+}
+
+impl HandClass {
+	fn is_pair(self) -> bool {
+		self.high == self.low
+	}
+
+	/// Combos of the hand, 6 for a pair, 4 suited and 12 offsuit
+	fn combos(self) -> Vec<CardSet> {
+		let suits: Vec<_> = Suit::iter().collect();
+		let mut combos = Vec::new();
+		for (i, &s1) in suits.iter().enumerate() {
+			for (j, &s2) in suits.iter().enumerate() {
+				let keep = if self.is_pair() {
+					i < j
+				} else {
+					match self.suited {
+						Suitedness::Suited => i == j,
+						Suitedness::Offsuit => i != j,
+						Suitedness::Any => true,
+					}
+				};
+				if keep {
+					let high = Card {
+						rank: rank(self.high),
+						suit: s1,
+					};
+					let low = Card {
+						rank: rank(self.low),
+						suit: s2,
+					};
+					combos.push(CardSet::from(high) | CardSet::from(low));
+				}
+			}
+		}
+		combos
+	}
+}
+
+/// Hand classes of a notation without weight
+fn expand(notation: &str) -> Result<Vec<HandClass>> {
+	let invalid = || bad_request_error(format!("invalid range: {notation}"));
+
+	if let Some(hand) = notation.strip_suffix('+') {
+		let class = parse_class(hand).ok_or_else(invalid)?;
+		let top = rank_index(Rank::A);
+		return Ok(if class.is_pair() {
+			(class.high..=top)
+				.map(|r| HandClass {
+					high: r,
+					low: r,
+					..class
+				})
+				.collect()
+		} else if class.high - class.low == 1 {
+			// connectors go up with the same gap
+			(0..=top - class.high)
+				.map(|i| HandClass {
+					high: class.high + i,
+					low: class.low + i,
+					..class
+				})
+				.collect()
+		} else {
+			(class.low..class.high)
+				.map(|low| HandClass { low, ..class })
+				.collect()
+		});
+	}
+
+	if let Some((from, to)) = notation.split_once('-') {
+		let from = parse_class(from.trim()).ok_or_else(invalid)?;
+		let to = parse_class(to.trim()).ok_or_else(invalid)?;
+		if from.suited != to.suited || from.is_pair() != to.is_pair() {
+			return Err(invalid());
+		}
+		let (from, to) = if from.low <= to.low {
+			(from, to)
+		} else {
+			(to, from)
+		};
+		return Ok(if from.is_pair() {
+			(from.high..=to.high)
+				.map(|r| HandClass {
+					high: r,
+					low: r,
+					..from
+				})
+				.collect()
+		} else if from.high == to.high {
+			(from.low..=to.low)
+				.map(|low| HandClass { low, ..from })
+				.collect()
+		} else if from.high - from.low == to.high - to.low {
+			(0..=to.low - from.low)
+				.map(|i| HandClass {
+					high: from.high + i,
+					low: from.low + i,
+					..from
+				})
+				.collect()
+		} else {
+			return Err(invalid());
+		});
+	}
+
+	Ok(vec![parse_class(notation).ok_or_else(invalid)?])
+}
+
+/// Parse a hand like `AKs`, `KQo`, `AK` or `QQ`
+fn parse_class(hand: &str) -> Option<HandClass> {
+	let mut chars = hand.chars();
+	let first = parse_rank(chars.next()?)?;
+	let second = parse_rank(chars.next()?)?;
+	let suited = match (chars.next(), chars.next()) {
+		(None, _) => Suitedness::Any,
+		(Some('s'), None) => Suitedness::Suited,
+		(Some('o'), None) => Suitedness::Offsuit,
+		_ => return None,
+	};
+	if first == second && suited != Suitedness::Any {
+		return None;
+	}
+
+	Some(HandClass {
+		high: first.max(second),
+		low: first.min(second),
+		suited,
+	})
+}
+
+fn parse_rank(rank: char) -> Option<usize> {
+	"23456789TJQKA"
+		.contains(rank)
+		.then(|| rank_index(Rank::parse(rank)))
+}
+
+fn parse_weight(weight: &str) -> Result<f64> {
+	match weight.parse::<f64>() {
+		Ok(weight) if weight > 0.0 && weight <= 1.0 => Ok(weight),
+		_ => Err(bad_request_error(format!("invalid weight: {weight}"))),
+	}
+}
+
+fn rank_index(rank: Rank) -> usize {
+	rank as usize
+}
+
+fn rank(index: usize) -> Rank {
+	Card::from_index(index).rank
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn count(range: &str) -> usize {
+		Range::parse(range).unwrap().len()
+	}
+
+	#[test]
+	fn test_parse() {
+		assert_eq!(count("AA"), 6);
+		assert_eq!(count("AKs"), 4);
+		assert_eq!(count("KQo"), 12);
+		assert_eq!(count("AK"), 16);
+		assert_eq!(count("QQ+"), 18);
+		assert_eq!(count("QQ-88"), 30);
+		assert_eq!(count("A5s-A2s"), 16);
+		assert_eq!(count("A2s-A5s"), 16);
+		assert_eq!(count("A9o+"), 60);
+		// 76s, 87s, 98s, T9s, JTs, QJs, KQs and AKs
+		assert_eq!(count("76s+"), 32);
+		assert_eq!(count("T8s-64s"), 20);
+		// AKs is in 76s+ too
+		assert_eq!(
+			count("QQ+, AKs, A5s-A2s, KQo, 76s+"),
+			18 + 4 + 16 + 12 + 32 - 4
+		);
+		assert_eq!(count("AK, AKs"), 16);
+		assert_eq!(count(""), 0);
+	}
+
+	#[test]
+	fn test_weight() {
+		let range = Range::parse("AK:0.5, AKs").unwrap();
+		assert_eq!(range.len(), 16);
+		let weights: f64 = range.combos.iter().map(|c| c.weight).sum();
+		assert!((weights - (12.0 * 0.5 + 4.0)).abs() < 1e-9);
+
+		assert!(Range::parse("AK:0").is_err());
+		assert!(Range::parse("AK:x").is_err());
+	}
+
+	#[test]
+	fn test_remove_cards() {
+		let mut range = Range::parse("AA, KK").unwrap();
+		range.remove_cards("SA HK".parse().unwrap());
+		assert_eq!(range.len(), 6);
+		assert!(range.combos.iter().all(|c| c.cards.len() == 2));
+	}
+
+	#[test]
+	fn test_invalid() {
+		for range in [
+			"AKx", "AAs", "A", "AKs-KQo", "A5s-K2s", "1A", "AKs+o", "QQ-AKs",
+		] {
+			assert!(Range::parse(range).is_err(), "{range}");
+		}
+	}
+}
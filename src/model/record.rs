@@ -1,14 +1,198 @@
-#[derive(Debug)]
+use std::fmt::Display;
+
+use rusqlite::{ToSql, types::FromSql};
+use serde::{Deserialize, Serialize};
+
+use super::{Card, Game, Round};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Action {
-	Bet(usize),
+	Ante,
+	SmallBlind,
+	BigBlind,
+	Check,
+	Call,
+	Bet,
+	Raise,
+	AllIn,
 	Fold,
 	Deal,
+	/// Hole cards shown at showdown or voluntarily after the game
+	Show,
+	/// Hole cards thrown away unseen at showdown
+	Muck,
+}
+
+impl Action {
+	/// Parse database representation
+	///
+	/// # Panics
+	///
+	/// Will panic if `action` is not valid
+	#[must_use]
+	pub fn parse(action: &str) -> Self {
+		match action {
+			"ante" => Self::Ante,
+			"sb" => Self::SmallBlind,
+			"bb" => Self::BigBlind,
+			"check" => Self::Check,
+			"call" => Self::Call,
+			"bet" => Self::Bet,
+			"raise" => Self::Raise,
+			"allin" => Self::AllIn,
+			"fold" => Self::Fold,
+			"deal" => Self::Deal,
+			"show" => Self::Show,
+			"muck" => Self::Muck,
+			_ => panic!("invalid action"),
+		}
+	}
+}
+
+impl Display for Action {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				Self::Ante => "ante",
+				Self::SmallBlind => "sb",
+				Self::BigBlind => "bb",
+				Self::Check => "check",
+				Self::Call => "call",
+				Self::Bet => "bet",
+				Self::Raise => "raise",
+				Self::AllIn => "allin",
+				Self::Fold => "fold",
+				Self::Deal => "deal",
+				Self::Show => "show",
+				Self::Muck => "muck",
+			}
+		)
+	}
+}
+
+impl FromSql for Action {
+	fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+		Ok(Self::parse(value.as_str()?))
+	}
+}
+
+impl ToSql for Action {
+	fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+		Ok(self.to_string().into())
+	}
 }
 
-#[derive(Debug)]
+/// An action or a card deal of a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Record {
-	pub seat: usize,
+	pub game_id: usize,
+	/// Order of the record in the game, starting from 0
+	pub seq: usize,
+	pub round: Round,
+	/// Seat position of the player, None when dealing common cards
+	pub position: Option<usize>,
+	pub guest_id: Option<usize>,
 	pub action: Action,
+	/// Chips put into the pot by this action
+	pub chips: usize,
+	/// Dealt cards
+	pub cards: Vec<Card>,
+}
+
+impl Record {
+	/// Record of an action of the current player
+	#[must_use]
+	pub fn action(game: &Game, guest_id: usize, action: Action, chips: usize) -> Self {
+		Self::player_action(game, game.position, guest_id, action, chips)
+	}
+
+	/// Record of an action of the player at `position`
+	#[must_use]
+	pub fn player_action(
+		game: &Game,
+		position: usize,
+		guest_id: usize,
+		action: Action,
+		chips: usize,
+	) -> Self {
+		Self {
+			game_id: game.id,
+			seq: 0,
+			round: game.round,
+			position: Some(position),
+			guest_id: Some(guest_id),
+			action,
+			chips,
+			cards: Vec::new(),
+		}
+	}
+
+	/// Record of dealing hole cards to a player
+	#[must_use]
+	pub fn deal_hand(game: &Game, position: usize, guest_id: usize, cards: Vec<Card>) -> Self {
+		Self {
+			cards,
+			..Self::player_action(game, position, guest_id, Action::Deal, 0)
+		}
+	}
+
+	/// Record of showing hole cards of a player
+	#[must_use]
+	pub fn show_hand(game: &Game, position: usize, guest_id: usize, cards: Vec<Card>) -> Self {
+		Self {
+			cards,
+			..Self::player_action(game, position, guest_id, Action::Show, 0)
+		}
+	}
+
+	/// Record of dealing common cards of the `round`
+	#[must_use]
+	pub fn deal_common(game: &Game, round: Round, cards: Vec<Card>) -> Self {
+		Self {
+			game_id: game.id,
+			seq: 0,
+			round,
+			position: None,
+			guest_id: None,
+			action: Action::Deal,
+			chips: 0,
+			cards,
+		}
+	}
+
+	/// Whether this is dealing hole cards to a player
+	#[must_use]
+	pub fn is_hole_cards(&self) -> bool {
+		self.action == Action::Deal && self.guest_id.is_some()
+	}
 }
 
 pub type Records = Vec<Record>;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_action_parse() {
+		let actions = [
+			Action::Ante,
+			Action::SmallBlind,
+			Action::BigBlind,
+			Action::Check,
+			Action::Call,
+			Action::Bet,
+			Action::Raise,
+			Action::AllIn,
+			Action::Fold,
+			Action::Deal,
+			Action::Show,
+			Action::Muck,
+		];
+		for action in actions {
+			assert_eq!(Action::parse(&action.to_string()), action);
+		}
+	}
+}
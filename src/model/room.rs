@@ -1,13 +1,18 @@
 use serde::{Deserialize, Serialize};
 
-use super::{Game, Guest, Seat};
+use super::{Guest, RoomSettings, Seat};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Room {
 	pub id: usize,
 	pub seats: [Option<Seat>; Self::MAX_SEATS],
+	/// The position of dealer button
+	pub button: usize,
 	/// The position of small blind
 	pub sb: usize,
+	/// The position of big blind
+	pub bb: usize,
+	pub settings: RoomSettings,
 }
 
 impl Room {
@@ -18,14 +23,18 @@ impl Room {
 		Self {
 			id,
 			seats: [const { None }; Self::MAX_SEATS],
+			button: 0,
 			sb: 0,
+			bb: 0,
+			settings: RoomSettings::default(),
 		}
 	}
 
 	#[must_use]
-	pub fn with_guest(id: usize, guest: &Guest) -> Self {
+	pub fn with_guest(id: usize, guest: &Guest, settings: RoomSettings) -> Self {
 		let mut table = Self::new(id);
-		table.seats[0] = Some(guest.clone().into());
+		table.seats[0] = Some(Seat::new(guest.clone(), settings.starting_stack));
+		table.settings = settings;
 		table
 	}
 
@@ -34,28 +43,36 @@ impl Room {
 		self.seats[position].as_ref().map(|s| &s.guest)
 	}
 
-	/// Correct the sb position
+	/// The first occupied seat after `position` clockwise
 	///
 	/// # Panics
 	///
 	/// Will panic if there is no player in this room
-	pub fn correct(&mut self) {
-		let mut sb;
-		for i in 0..Self::MAX_SEATS {
-			sb = (self.sb + i) % Self::MAX_SEATS;
-			if self.seats[sb].is_some() {
-				self.sb = sb;
-				return;
+	#[must_use]
+	pub fn next_seat(&self, position: usize) -> usize {
+		for i in 1..=Self::MAX_SEATS {
+			let p = (position + i) % Self::MAX_SEATS;
+			if self.seats[p].is_some() {
+				return p;
 			}
 		}
 
 		panic!("no player in the room");
 	}
 
-	/// Pass sb to the next guest
-	pub fn pass_sb(&mut self) {
-		self.sb += 1;
-		self.correct();
+	/// Pass the button to the next guest and derive the blinds from it
+	///
+	/// This uses the moving button rule: the button always moves to the next
+	/// seated guest, and the next two guests post the small blind and the big
+	/// blind. Heads-up the button posts the small blind.
+	pub fn pass_button(&mut self) {
+		self.button = self.next_seat(self.button);
+		if self.count() == 2 {
+			self.sb = self.button;
+		} else {
+			self.sb = self.next_seat(self.button);
+		}
+		self.bb = self.next_seat(self.sb);
 	}
 
 	/// Number of users
@@ -73,6 +90,15 @@ impl Room {
 			.count()
 	}
 
+	/// Number of players who can still act, i.e. neither fold nor allin
+	#[must_use]
+	pub fn active_count(&self) -> usize {
+		self.seats
+			.iter()
+			.filter(|s| s.as_ref().is_some_and(|s| !s.fold && !s.allin()))
+			.count()
+	}
+
 	/// Whether all remaining players allin
 	#[must_use]
 	pub fn all_allin(&self) -> bool {
@@ -86,10 +112,10 @@ impl Room {
 	#[must_use]
 	pub fn all_ready(&self) -> bool {
 		for seat in &self.seats {
-			if let Some(seat) = &seat {
-				if !seat.ready {
-					return false;
-				}
+			if let Some(seat) = &seat
+				&& !seat.ready
+			{
+				return false;
 			}
 		}
 
@@ -123,18 +149,18 @@ impl Room {
 		if empty == Self::MAX_SEATS {
 			None
 		} else {
-			self.seats[empty] = Some(guest.into());
+			self.seats[empty] = Some(Seat::new(guest, self.settings.starting_stack));
 			Some(empty)
 		}
 	}
 
 	/// Return if the guest is on the table
-	pub fn has_guest(&mut self, guest_id: usize) -> bool {
+	pub fn has_guest(&self, guest_id: usize) -> bool {
 		for seat in &self.seats {
-			if let Some(seat) = &seat {
-				if seat.guest.id == guest_id {
-					return true;
-				}
+			if let Some(seat) = &seat
+				&& seat.guest.id == guest_id
+			{
+				return true;
 			}
 		}
 		false
@@ -148,10 +174,10 @@ impl Room {
 	#[must_use]
 	pub fn is_ready(&self, guest_id: usize) -> Option<bool> {
 		for seat in &self.seats {
-			if let Some(seat) = &seat {
-				if seat.guest.id == guest_id {
-					return Some(seat.ready);
-				}
+			if let Some(seat) = &seat
+				&& seat.guest.id == guest_id
+			{
+				return Some(seat.ready);
 			}
 		}
 		None
@@ -161,19 +187,18 @@ impl Room {
 	///
 	/// # Return
 	///
-	/// Seat position of the guest, None if not found
-	///
-	/// # Panics
-	///
-	/// Will panic if the guest's stack less than 10
+	/// Seat position of the guest, None if not found or the guest's stack is
+	/// less than the min stack of the room
 	pub fn ready(&mut self, guest_id: usize) -> Option<usize> {
 		for (i, seat) in &mut self.seats.iter_mut().enumerate() {
-			if let Some(seat) = seat.as_mut() {
-				if seat.guest.id == guest_id {
-					assert!(seat.stack >= 10);
-					seat.ready = true;
-					return Some(i);
+			if let Some(seat) = seat.as_mut()
+				&& seat.guest.id == guest_id
+			{
+				if seat.stack < self.settings.min_stack {
+					return None;
 				}
+				seat.ready = true;
+				return Some(i);
 			}
 		}
 		None
@@ -186,29 +211,50 @@ impl Room {
 	/// Seat position of the guest, None if not found
 	pub fn unready(&mut self, guest_id: usize) -> Option<usize> {
 		for (i, seat) in &mut self.seats.iter_mut().enumerate() {
-			if let Some(seat) = seat.as_mut() {
-				if seat.guest.id == guest_id {
-					seat.ready = false;
-					return Some(i);
-				}
+			if let Some(seat) = seat.as_mut()
+				&& seat.guest.id == guest_id
+			{
+				seat.ready = false;
+				return Some(i);
 			}
 		}
 		None
 	}
 
-	pub fn new_game(&mut self, game_id: usize) -> Game {
-		let mut sb = 0;
+	/// Split the pot evenly among the winners
+	///
+	/// Odd chips are awarded one at a time to the winners in clockwise order,
+	/// starting from the first seat left of the button.
+	///
+	/// # Return
+	///
+	/// Chips won by each winner, in the same order as `positions`
+	///
+	/// # Panics
+	///
+	/// Will panic if `positions` is empty
+	#[must_use]
+	pub fn split_pot(&self, pot: usize, positions: &[usize]) -> Vec<usize> {
+		assert!(!positions.is_empty());
+		let share = pot / positions.len();
+		let mut odd = pot % positions.len();
+		let mut chips = vec![share; positions.len()];
+
 		for i in 0..Self::MAX_SEATS {
-			sb = (self.sb + i) % Self::MAX_SEATS;
-			if self.seats[sb].is_some() {
+			if odd == 0 {
 				break;
 			}
+			let p = (self.button + 1 + i) % Self::MAX_SEATS;
+			if let Some(j) = positions.iter().position(|&x| x == p) {
+				chips[j] += 1;
+				odd -= 1;
+			}
 		}
-		self.sb = (sb + 1) % Self::MAX_SEATS;
 
-		Game::new(game_id, self.id, self.sb)
+		chips
 	}
 
+	/// Max bet of the current round
 	#[must_use]
 	pub fn max_bet(&self) -> usize {
 		let mut max_bet = 0;
@@ -217,10 +263,108 @@ impl Room {
 				if seat.fold {
 					continue;
 				}
-				max_bet = max_bet.max(seat.bet);
+				max_bet = max_bet.max(seat.round_bet);
 			}
 		}
 
 		max_bet
 	}
+
+	/// Chips the player at `position` needs to call in the current round
+	///
+	/// # Panics
+	///
+	/// Will panic if the seat is empty
+	#[must_use]
+	pub fn call_amount(&self, position: usize) -> usize {
+		let seat = self.seats[position].as_ref().unwrap();
+		(self.max_bet() - seat.round_bet).min(seat.stack)
+	}
+
+	/// Clear bets of the last round before dealing the next round
+	pub fn new_round(&mut self) {
+		for seat in self.seats.iter_mut().flatten() {
+			seat.round_bet = 0;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_split_pot_even() {
+		let room = Room::new(1);
+		assert_eq!(room.split_pot(100, &[1, 3]), vec![50, 50]);
+		assert_eq!(room.split_pot(99, &[0, 4, 7]), vec![33, 33, 33]);
+	}
+
+	#[test]
+	fn test_split_pot_odd_chips() {
+		let mut room = Room::new(1);
+		room.button = 2;
+		// seat 5 is the first winner left of the button
+		assert_eq!(room.split_pot(101, &[1, 5]), vec![50, 51]);
+		// seats 8 and 1 get the odd chips, seat 2 is last
+		assert_eq!(room.split_pot(11, &[1, 2, 8]), vec![4, 3, 4]);
+	}
+
+	#[test]
+	fn test_round_bets() {
+		let mut room = Room::with_guest(1, &Guest::new(1, "Alice"), RoomSettings::default());
+		room.insert(Guest::new(2, "Bob"));
+		for (seat, bet) in room.seats.iter_mut().flatten().zip([30, 10]) {
+			seat.stack -= bet;
+			seat.bet += bet;
+			seat.round_bet += bet;
+		}
+		assert_eq!(room.max_bet(), 30);
+		assert_eq!(room.call_amount(0), 0);
+		assert_eq!(room.call_amount(1), 20);
+
+		room.new_round();
+		assert_eq!(room.max_bet(), 0);
+		assert_eq!(room.call_amount(1), 0);
+		assert_eq!(room.seats[1].as_ref().unwrap().bet, 10);
+	}
+
+	#[test]
+	fn test_ready() {
+		let mut room = Room::with_guest(1, &Guest::new(1, "Alice"), RoomSettings::default());
+		room.seats[3] = Some(Seat::new(Guest::new(2, "Bob"), 9));
+
+		assert_eq!(room.ready(1), Some(0));
+		assert_eq!(room.is_ready(1), Some(true));
+		// less than the min stack of 10
+		assert_eq!(room.ready(2), None);
+		assert_eq!(room.is_ready(2), Some(false));
+		assert_eq!(room.ready(3), None);
+	}
+
+	#[test]
+	fn test_pass_button() {
+		let mut room = Room::new(1);
+		for (i, p) in [1, 4, 6].into_iter().enumerate() {
+			room.seats[p] = Some(Seat::new(Guest::new(i + 1, "guest"), 1000));
+		}
+
+		room.pass_button();
+		assert_eq!((room.button, room.sb, room.bb), (1, 4, 6));
+		room.pass_button();
+		assert_eq!((room.button, room.sb, room.bb), (4, 6, 1));
+		room.pass_button();
+		assert_eq!((room.button, room.sb, room.bb), (6, 1, 4));
+	}
+
+	#[test]
+	fn test_pass_button_heads_up() {
+		let mut room = Room::with_guest(1, &Guest::new(1, "Alice"), RoomSettings::default());
+		room.seats[7] = Some(Seat::new(Guest::new(2, "Bob"), 1000));
+
+		room.pass_button();
+		assert_eq!((room.button, room.sb, room.bb), (7, 7, 0));
+		room.pass_button();
+		assert_eq!((room.button, room.sb, room.bb), (0, 0, 7));
+	}
 }
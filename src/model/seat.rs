@@ -7,26 +7,29 @@ pub struct Seat {
 	pub guest: Guest,
 	pub ready: bool,
 	pub stack: usize,
+	/// Total chips bet in the game
 	pub bet: usize,
+	/// Chips bet in the current round
+	pub round_bet: usize,
 	pub fold: bool,
 }
 
 impl Seat {
-	/// Whether the guest has allined
 	#[must_use]
-	pub fn allin(&self) -> bool {
-		self.stack == 0
-	}
-}
-
-impl From<Guest> for Seat {
-	fn from(guest: Guest) -> Self {
+	pub fn new(guest: Guest, stack: usize) -> Self {
 		Self {
 			guest,
 			ready: false,
-			stack: 1000,
+			stack,
 			bet: 0,
+			round_bet: 0,
 			fold: false,
 		}
 	}
+
+	/// Whether the guest has allined
+	#[must_use]
+	pub fn allin(&self) -> bool {
+		self.stack == 0
+	}
 }
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, bad_request_error};
+
+/// Stakes and stacks of a room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomSettings {
+	pub small_blind: usize,
+	pub big_blind: usize,
+	/// Ante posted by every player, or by the big blind alone if
+	/// `big_blind_ante` is set, 0 for no ante
+	pub ante: usize,
+	pub big_blind_ante: bool,
+	/// Stack of a guest when joining the room
+	pub starting_stack: usize,
+	/// Minimum stack to get ready for a game
+	pub min_stack: usize,
+}
+
+impl Default for RoomSettings {
+	fn default() -> Self {
+		Self {
+			small_blind: 1,
+			big_blind: 2,
+			ante: 0,
+			big_blind_ante: false,
+			starting_stack: 1000,
+			min_stack: 10,
+		}
+	}
+}
+
+impl RoomSettings {
+	/// Check whether the settings are playable
+	///
+	/// # Errors
+	///
+	/// Return bad request error with the reason if not
+	pub fn validate(&self) -> Result<()> {
+		if self.small_blind == 0 {
+			return Err(bad_request_error("small blind should be positive"));
+		}
+		if self.big_blind < self.small_blind {
+			return Err(bad_request_error(
+				"big blind should be no less than small blind",
+			));
+		}
+		if self.min_stack == 0 {
+			return Err(bad_request_error("min stack should be positive"));
+		}
+		if self.starting_stack < self.min_stack.max(self.big_blind) {
+			return Err(bad_request_error(
+				"starting stack should be no less than min stack and big blind",
+			));
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_default_valid() {
+		assert!(RoomSettings::default().validate().is_ok());
+	}
+
+	#[test]
+	fn test_invalid() {
+		let default = RoomSettings::default();
+		let settings = [
+			RoomSettings {
+				small_blind: 0,
+				..default.clone()
+			},
+			RoomSettings {
+				small_blind: 5,
+				big_blind: 2,
+				..default.clone()
+			},
+			RoomSettings {
+				min_stack: 0,
+				..default.clone()
+			},
+			RoomSettings {
+				starting_stack: 5,
+				..default.clone()
+			},
+			RoomSettings {
+				big_blind: 2000,
+				..default
+			},
+		];
+		for settings in settings {
+			assert!(settings.validate().is_err());
+		}
+	}
+}